@@ -0,0 +1,92 @@
+//*****************************************************************************
+//   Service Worker (release builds only, generated as `/service-worker.js`)
+//*****************************************************************************
+
+// Changes with content of any precached file
+const CACHE_NAME = "shards-browser-<!-- #echo "cache_version" -->";
+const CACHE_PREFIX = "shards-browser-";
+
+// Fingerprinted files outlive versions (wasm downloaded by previous version
+// is reused while still listed in `IMMUTABLE`)
+const IMMUTABLE_CACHE_NAME = CACHE_PREFIX + "immutable";
+
+// Pages, styles, fonts and wasm loader (wasm and glue modules are cached once
+// page loads them: only variant page picks is downloaded)
+const PRECACHE = <!-- #echo "precache" -->;
+
+// Fingerprinted files never change: cache first (cached on first fetch)
+const IMMUTABLE = new Set(<!-- #echo "immutable" -->);
+
+self.addEventListener("install", event => {
+    event.waitUntil(
+        caches.open(CACHE_NAME).then(cache => cache.addAll(PRECACHE))
+    );
+});
+
+// Drop caches of previous versions and fingerprinted files no longer built
+self.addEventListener("activate", event => {
+    event.waitUntil(
+        caches.keys()
+            .then(names => Promise.all(
+                names
+                    .filter(name => name.startsWith(CACHE_PREFIX)
+                        && name !== CACHE_NAME && name !== IMMUTABLE_CACHE_NAME)
+                    .map(name => caches.delete(name))
+            ))
+            .then(() => caches.open(IMMUTABLE_CACHE_NAME))
+            .then(cache => cache.keys().then(requests => Promise.all(
+                requests
+                    .filter(request => !IMMUTABLE.has(new URL(request.url).pathname))
+                    .map(request => cache.delete(request))
+            )))
+            .then(() => self.clients.claim())
+    );
+});
+
+// Page activates waiting version on user request ("new version available")
+self.addEventListener("message", event => {
+    if (event.data === "skip-waiting") {
+        self.skipWaiting();
+    }
+});
+
+self.addEventListener("fetch", event => {
+    const request = event.request;
+    const url = new URL(request.url);
+    if (request.method !== "GET" || url.origin !== self.location.origin) {
+        return;
+    }
+
+    if (IMMUTABLE.has(url.pathname)) {
+        event.respondWith(cacheFirst(request));
+    } else {
+        event.respondWith(networkFirst(request));
+    }
+});
+
+function cacheFirst(request) {
+    return caches.match(request)
+        .then(cached => cached || fetchAndCache(request, IMMUTABLE_CACHE_NAME));
+}
+
+// Pages (`index.html`) and fixed name files: fresh when online
+function networkFirst(request) {
+    return fetchAndCache(request, CACHE_NAME).catch(err =>
+        caches.match(request).then(cached => {
+            if (!cached) {
+                throw err;
+            }
+            return cached;
+        })
+    );
+}
+
+function fetchAndCache(request, cacheName) {
+    return fetch(request).then(response => {
+        if (response.ok) {
+            const copy = response.clone();
+            caches.open(cacheName).then(cache => cache.put(request, copy));
+        }
+        return response;
+    });
+}
@@ -0,0 +1,93 @@
+//*****************************************************************************
+//   Wasm Loader (generated next to wasm modules by builds)
+//*****************************************************************************
+
+// Library name of each built variant -> its glue module, wasm module and
+// (uncompressed) size of the latter (`Content-Length` of compressed response
+// does not match streamed bytes). Front-page picks variant among these.
+export const WASM_MODULES = <!-- #echo "wasm_modules" -->;
+
+const WASM_MIME_TYPE = "application/wasm";
+
+// Library name of initialized variant (started workers load same one)
+let loadedName = null;
+
+// Import wasm-bindgen glue module of library `name`
+export function importGlue(name) {
+    return import(WASM_MODULES[name].glue);
+}
+
+// Initialize wasm-bindgen `glue` of library `name` reporting download progress
+// (0-100) to `onProgress`
+export function initWasm(glue, name, onProgress) {
+    const wasm = WASM_MODULES[name];
+    loadedName = name;
+    return fetch(new URL(wasm.path, import.meta.url)).then(response => {
+        if (!response.ok) {
+            throw new Error(`Failed to fetch ${wasm.path}: ${response.status}`);
+        }
+        const tracked = trackProgress(response, wasm.size, onProgress);
+
+        // `instantiateStreaming` rejects other MIME types: buffer whole module
+        if (response.headers.get("Content-Type") !== WASM_MIME_TYPE) {
+            console.warn(`${wasm.path} is not served as ${WASM_MIME_TYPE}, falling back to buffered instantiation`);
+            return tracked.arrayBuffer().then(bytes => glue.default({ module_or_path: bytes }));
+        }
+        return glue.default({ module_or_path: tracked });
+    });
+}
+
+// Same response with body counting streamed bytes
+function trackProgress(response, size, onProgress) {
+    const reader = response.body.getReader();
+    let loaded = 0;
+    let reported = -1;
+
+    const body = new ReadableStream({
+        pull(controller) {
+            return reader.read().then(({ done, value }) => {
+                if (done) {
+                    onProgress(100);
+                    controller.close();
+                    return;
+                }
+                loaded += value.byteLength;
+                const percent = Math.min(99, Math.floor(loaded * 100 / size));
+                if (percent !== reported) {
+                    reported = percent;
+                    onProgress(percent);
+                }
+                controller.enqueue(value);
+            });
+        },
+        cancel(reason) {
+            return reader.cancel(reason);
+        },
+    });
+
+    return new Response(body, {
+        status: response.status,
+        statusText: response.statusText,
+        headers: response.headers,
+    });
+}
+
+// Threads of shared memory build (`--threads`): `shards_browser::threads`
+// starts each one as module worker running this loader, which instantiates
+// same `module` over same `memory` and runs `work` (pointer into that memory)
+self.shardsThreads = {
+    spawn(module, memory, work) {
+        const worker = new Worker(import.meta.url, { type: "module", name: loadedName });
+        worker.postMessage({ name: loadedName, module, memory, work });
+    },
+};
+
+if (typeof WorkerGlobalScope !== "undefined" && self instanceof WorkerGlobalScope) {
+    self.addEventListener("message", ({ data: { name, module, memory, work } }) => {
+        loadedName = name;
+        importGlue(name)
+            .then(glue => glue.default({ module_or_path: module, memory })
+                .then(() => glue.worker_entry_point(work)))
+            .catch(error => console.error("Failed to start worker:", error));
+    }, { once: true });
+}
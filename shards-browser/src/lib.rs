@@ -3,6 +3,10 @@ use wasm_bindgen::prelude::*;
 
 use std::sync::OnceLock;
 
+pub mod threads;
+#[cfg(target_arch = "wasm32")]
+mod update;
+
 static START_SUCCESS: OnceLock<bool> = OnceLock::new();
 
 fn first_startup() -> bool {
@@ -10,6 +14,14 @@ fn first_startup() -> bool {
 
     log::debug!("Shards browser started!");
 
+    if let Err(e) = threads::spawn(|| log::debug!("Background thread started!"))
+    {
+        log::warn!("No background threads: {}", e);
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    update::listen();
+
     return true;
 }
 
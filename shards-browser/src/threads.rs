@@ -0,0 +1,73 @@
+//! Background threads.
+//!
+//! `std::thread::spawn` is unsupported on `wasm32-unknown-unknown`: shared
+//! memory builds (`+atomics`) start threads as web workers running wasm
+//! loader of front-page, which instantiates same module over same memory and
+//! calls `worker_entry_point`. Other wasm builds have no threads.
+
+use std::io;
+
+#[cfg(all(target_arch = "wasm32", target_feature = "atomics"))]
+use wasm_bindgen::prelude::*;
+
+/// Work passed to worker by pointer into shared memory
+#[cfg(all(target_arch = "wasm32", target_feature = "atomics"))]
+type Work = Box<dyn FnOnce() + Send>;
+
+#[cfg(all(target_arch = "wasm32", target_feature = "atomics"))]
+#[wasm_bindgen]
+extern "C" {
+    /// `shardsThreads.spawn` of wasm loader
+    #[wasm_bindgen(catch, js_namespace = shardsThreads, js_name = spawn)]
+    fn spawn_worker(
+        module: JsValue,
+        memory: JsValue,
+        work: u32,
+    ) -> Result<(), JsValue>;
+}
+
+/// Run `f` on background thread (detached)
+#[cfg(not(target_arch = "wasm32"))]
+pub fn spawn<F>(f: F) -> io::Result<()>
+where
+    F: FnOnce() + Send + 'static,
+{
+    std::thread::Builder::new().spawn(f).map(drop)
+}
+
+/// Run `f` on background thread (detached web worker)
+#[cfg(all(target_arch = "wasm32", target_feature = "atomics"))]
+pub fn spawn<F>(f: F) -> io::Result<()>
+where
+    F: FnOnce() + Send + 'static,
+{
+    let work: Box<Work> = Box::new(Box::new(f));
+    let work = Box::into_raw(work);
+
+    spawn_worker(wasm_bindgen::module(), wasm_bindgen::memory(), work as u32)
+        .map_err(|e| {
+            // worker never started: work is still ours
+            drop(unsafe { Box::from_raw(work) });
+            io::Error::other(format!("failed to start worker: {:?}", e))
+        })
+}
+
+/// Run `f` on background thread (unsupported without shared memory)
+#[cfg(all(target_arch = "wasm32", not(target_feature = "atomics")))]
+pub fn spawn<F>(_f: F) -> io::Result<()>
+where
+    F: FnOnce() + Send + 'static,
+{
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "threads require shared memory build",
+    ))
+}
+
+/// Called by wasm loader in worker started by `spawn`
+#[cfg(all(target_arch = "wasm32", target_feature = "atomics"))]
+#[wasm_bindgen]
+pub fn worker_entry_point(work: u32) {
+    let work = unsafe { Box::from_raw(work as *mut Work) };
+    work();
+}
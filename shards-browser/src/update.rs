@@ -0,0 +1,52 @@
+//! New version prompt.
+//!
+//! Service worker of release builds installs new version in background and
+//! front-page announces it (`shardsUpdate`, `shards-update-available` event):
+//! user is asked to reload, which activates waiting version.
+
+use wasm_bindgen::prelude::*;
+
+const UPDATE_EVENT: &str = "shards-update-available";
+
+const UPDATE_PROMPT: &str =
+    "New version of Shards Browser is available. Reload now?";
+
+#[wasm_bindgen]
+extern "C" {
+    /// `shardsUpdate` of front-page
+    type ShardsUpdate;
+
+    #[wasm_bindgen(thread_local_v2, js_name = shardsUpdate)]
+    static SHARDS_UPDATE: ShardsUpdate;
+
+    #[wasm_bindgen(method, getter)]
+    fn available(this: &ShardsUpdate) -> bool;
+
+    /// Activate waiting version and reload page
+    #[wasm_bindgen(method)]
+    fn apply(this: &ShardsUpdate);
+
+    #[wasm_bindgen(js_name = addEventListener)]
+    fn add_event_listener(kind: &str, listener: &Closure<dyn FnMut()>);
+
+    fn confirm(message: &str) -> bool;
+}
+
+/// Prompt for update now if one is waiting and on every announced one
+pub fn listen() {
+    if SHARDS_UPDATE.with(ShardsUpdate::available) {
+        prompt();
+    }
+
+    // page lives as long as listener
+    let listener = Closure::<dyn FnMut()>::new(prompt);
+    add_event_listener(UPDATE_EVENT, &listener);
+    listener.forget();
+}
+
+fn prompt() {
+    log::info!("New version available");
+    if confirm(UPDATE_PROMPT) {
+        SHARDS_UPDATE.with(ShardsUpdate::apply);
+    }
+}
@@ -1,9 +1,12 @@
 use std::{
-    cell::RefCell,
     env, fs,
     io::{Error, ErrorKind, Read, Result},
     path::{Path, PathBuf},
     process::{Command, ExitStatus, Stdio},
+    sync::{
+        Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
     thread,
 };
 
@@ -16,6 +19,111 @@ use crate::paths;
 pub type TaskResult = Result<()>;
 pub const OK: TaskResult = Ok(());
 
+/// Task option overriding `FailurePolicy::for_build`
+const FAILURE_POLICY_OPTION: &str = "--failure-policy";
+
+/// How operations over many files (file trees, task swarms) handle failures
+/// of single files
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FailurePolicy {
+    /// stop on first failure
+    FailFast,
+    /// handle every file, fail at the end if anything failed
+    CollectAndFail,
+    /// handle every file, only log failures
+    BestEffort,
+}
+
+impl FailurePolicy {
+    /// `--failure-policy <fail-fast|collect-and-fail|best-effort>` if passed,
+    /// release builds fail if anything went wrong, developer ones keep going
+    pub fn for_build(release: bool) -> Self {
+        match task_option(FAILURE_POLICY_OPTION).as_deref() {
+            Some("fail-fast") => FailurePolicy::FailFast,
+            Some("collect-and-fail") => FailurePolicy::CollectAndFail,
+            Some("best-effort") => FailurePolicy::BestEffort,
+            Some(other) => {
+                log::warn!(
+                    "[xtask] Unknown {} {}, using default",
+                    FAILURE_POLICY_OPTION,
+                    other
+                );
+                Self::default_for_build(release)
+            }
+            None => Self::default_for_build(release),
+        }
+    }
+
+    fn default_for_build(release: bool) -> Self {
+        if release {
+            FailurePolicy::CollectAndFail
+        } else {
+            FailurePolicy::BestEffort
+        }
+    }
+}
+
+/// Outcome of file tree operation: handled files, files skipped by filter
+/// and failures with causes (paths are relative to tree root, failures of
+/// listing directories have full paths)
+#[derive(Debug, Default)]
+pub struct FileTreeReport {
+    pub succeeded: Vec<PathBuf>,
+    pub skipped: Vec<PathBuf>,
+    pub failed: Vec<(PathBuf, Error)>,
+}
+
+impl FileTreeReport {
+    /// Record result of `path` (`Ok(false)` if it was skipped), fails right
+    /// away for `FailFast`
+    fn record(
+        &mut self,
+        path: PathBuf,
+        result: Result<bool>,
+        policy: FailurePolicy,
+    ) -> TaskResult {
+        match result {
+            Ok(true) => self.succeeded.push(path),
+            Ok(false) => self.skipped.push(path),
+            Err(e) if policy == FailurePolicy::FailFast => {
+                return Err(Error::new(
+                    e.kind(),
+                    format!("{}: {}", path.display(), e),
+                ));
+            }
+            Err(e) => {
+                log::error!("[xtask] {}: {}", path.display(), e);
+                self.failed.push((path, e));
+            }
+        }
+        OK
+    }
+
+    /// Apply policy to collected failures (already logged)
+    fn finish(self, policy: FailurePolicy) -> Result<Self> {
+        log::debug!(
+            "[xtask] File tree: {} succeeded, {} skipped, {} failed",
+            self.succeeded.len(),
+            self.skipped.len(),
+            self.failed.len()
+        );
+        if self.failed.is_empty() || policy == FailurePolicy::BestEffort {
+            return Ok(self);
+        }
+        let failures: Vec<_> = self
+            .failed
+            .iter()
+            .map(|(path, e)| format!("{}: {}", path.display(), e))
+            .collect();
+        Err(Error::other(format!(
+            "{} of {} files failed: {}",
+            self.failed.len(),
+            self.failed.len() + self.succeeded.len(),
+            failures.join("; ")
+        )))
+    }
+}
+
 /// Some extra methods for Path
 pub trait DistributionPath {
     fn contains_any_extension(&self, extensions: &[&[u8]]) -> bool;
@@ -24,19 +132,28 @@ pub trait DistributionPath {
 
     fn cut_children(&self, depth: usize) -> Option<&Path>;
 
-    fn for_each_file_recursively<'a, F>(&self, f: F) -> TaskResult
+    fn for_each_file_recursively<F>(
+        &self,
+        policy: FailurePolicy,
+        f: F,
+    ) -> Result<FileTreeReport>
     where
-        F: FnMut(&Path) -> () + 'a;
+        F: FnMut(&Path) -> TaskResult;
 
-    fn copy_file_tree(&self, dest_dir: &Path) -> TaskResult;
+    fn copy_file_tree(
+        &self,
+        dest_dir: &Path,
+        policy: FailurePolicy,
+    ) -> Result<FileTreeReport>;
 
-    fn copy_file_tree_filtered<'a, P>(
+    fn copy_file_tree_filtered<P>(
         &self,
         dest_dir: &Path,
+        policy: FailurePolicy,
         predicate: P,
-    ) -> TaskResult
+    ) -> Result<FileTreeReport>
     where
-        P: FnMut(&Path) -> bool + 'a;
+        P: FnMut(&Path) -> bool;
 }
 
 impl DistributionPath for Path {
@@ -149,82 +266,105 @@ impl DistributionPath for Path {
     // File System Manipulation Primitives
     ////////////////////////////////////////////////////////////////////////////////
 
-    /// DFS throughout `base_dir` based on lazy iterators
+    /// DFS throughout `base_dir`, failures of `f` and of listing directories
+    /// are handled according to `policy`
     ///
     /// Note: `f` receives path relative to `base_dir`
-    fn for_each_file_recursively<'a, F>(&self, f: F) -> TaskResult
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::Error;
+    /// use xtask::core_dist::{DistributionPath, FailurePolicy, OK};
+    ///
+    /// let dir = tempfile::tempdir().unwrap();
+    /// std::fs::create_dir(dir.path().join("sub")).unwrap();
+    /// std::fs::write(dir.path().join("a.txt"), "").unwrap();
+    /// std::fs::write(dir.path().join("sub/b.txt"), "").unwrap();
+    ///
+    /// let fail_a = |path: &std::path::Path| {
+    ///     if path.ends_with("a.txt") { Err(Error::other("a")) } else { OK }
+    /// };
+    ///
+    /// let report = dir
+    ///     .path()
+    ///     .for_each_file_recursively(FailurePolicy::BestEffort, fail_a)
+    ///     .unwrap();
+    /// assert_eq!(report.succeeded.len(), 1);
+    /// assert_eq!(report.failed.len(), 1);
+    ///
+    /// assert!(
+    ///     dir.path()
+    ///         .for_each_file_recursively(FailurePolicy::CollectAndFail, fail_a)
+    ///         .is_err()
+    /// );
+    /// ```
+    fn for_each_file_recursively<F>(
+        &self,
+        policy: FailurePolicy,
+        mut f: F,
+    ) -> Result<FileTreeReport>
     where
-        F: FnMut(&Path) -> () + 'a,
+        F: FnMut(&Path) -> TaskResult,
     {
-        // Note: pop_files returns iterator of directories
-
-        // Wrap for multiple mutable borrows inside pop_files
-        let f = RefCell::new(f);
-        let mut remaining_dirs_iterators = {
-            let inner_dirs_iterator = pop_files(self, self, &f)?;
-            vec![inner_dirs_iterator]
-        };
-
-        // DFS loop (pop stack top)
-        while let Some(mut dirs) = remaining_dirs_iterators.pop() {
-            match dirs.next() {
-                Some(ref new_dir) => {
-                    log::debug!(
-                        "[xtask] processing recursively dir {}",
-                        new_dir.display()
-                    );
-                    // bring parent level file-iterator back first
-                    remaining_dirs_iterators.push(dirs);
-                    // try add child level file-iterator
-                    match pop_files(self, new_dir, &f) {
-                        Ok(iterator) => remaining_dirs_iterators.push(iterator),
-                        Err(e) => log::error!(
-                            "[xtask] Failed to list {}: {}",
-                            new_dir.display(),
-                            e
-                        ),
-                    }
-                }
-                None => (),
-            };
-        }
-
-        OK
+        walk_file_tree(self, policy, |relative_path| {
+            f(relative_path).map(|()| true)
+        })
     }
 
     /// Copy all files from `from_dir` to `dest_dir`.
-    /// Does not stop on errors (just logs and skips).
-    fn copy_file_tree(&self, dest_dir: &Path) -> TaskResult {
-        self.copy_file_tree_filtered(dest_dir, |_| true)
+    fn copy_file_tree(
+        &self,
+        dest_dir: &Path,
+        policy: FailurePolicy,
+    ) -> Result<FileTreeReport> {
+        self.copy_file_tree_filtered(dest_dir, policy, |_| true)
     }
 
-    /// Copy files from `from_dir` to `dest_dir` passing `predicate`.
-    /// Does not stop on errors (just logs and skips).
-    fn copy_file_tree_filtered<'a, P>(
+    /// Copy files from `from_dir` to `dest_dir` passing `predicate` (others
+    /// are reported as skipped).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::fs;
+    /// use std::path::{Path, PathBuf};
+    /// use xtask::core_dist::{DistributionPath, FailurePolicy};
+    ///
+    /// let from = tempfile::tempdir().unwrap();
+    /// let dest = tempfile::tempdir().unwrap();
+    /// fs::write(from.path().join("a.css"), "").unwrap();
+    /// fs::write(from.path().join("b.html"), "").unwrap();
+    ///
+    /// let report = from
+    ///     .path()
+    ///     .copy_file_tree_filtered(dest.path(), FailurePolicy::FailFast, |p| {
+    ///         p != Path::new("b.html")
+    ///     })
+    ///     .unwrap();
+    /// assert_eq!(report.succeeded, [PathBuf::from("a.css")]);
+    /// assert_eq!(report.skipped, [PathBuf::from("b.html")]);
+    /// assert!(dest.path().join("a.css").is_file());
+    /// assert!(!dest.path().join("b.html").exists());
+    /// ```
+    fn copy_file_tree_filtered<P>(
         &self,
         dest_dir: &Path,
+        policy: FailurePolicy,
         mut predicate: P,
-    ) -> TaskResult
+    ) -> Result<FileTreeReport>
     where
-        P: FnMut(&Path) -> bool + 'a,
+        P: FnMut(&Path) -> bool,
     {
-        self.for_each_file_recursively(|relative_path| {
-            // note: we only log errors and return nothing here
-            if ! predicate(relative_path) {
-                return;
+        walk_file_tree(self, policy, |relative_path| {
+            if !predicate(relative_path) {
+                return Ok(false);
             }
             let from_path = self.join(relative_path);
             let dest_path = dest_dir.join(relative_path);
             // make necessary directories
-            if let Some(dest_path_parent) = dest_path.parent()
-                && let Err(e) = make_each_directory(dest_path_parent)
-            {
-                log::error!(
-                    "[xtask] Error creating directory {} while copying file tree: {}",
-                    dest_path_parent.display(),
-                    e
-                );
-                return;
+            if let Some(dest_path_parent) = dest_path.parent() {
+                make_each_directory(dest_path_parent)?;
             }
             // copy
             log::info!(
@@ -232,15 +372,7 @@ impl DistributionPath for Path {
                 from_path.display(),
                 dest_path.display()
             );
-            match fs::copy(&from_path, &dest_path) {
-                Err(e) => log::error!(
-                    "[xtask] Error copying {} to {}: {}",
-                    from_path.display(),
-                    dest_path.display(),
-                    e
-                ),
-                Ok(_) => {}
-            }
+            fs::copy(&from_path, &dest_path).map(|_| true)
         })
     }
 }
@@ -254,6 +386,139 @@ pub fn make_each_directory(path: &Path) -> TaskResult {
     fs::create_dir_all(path)
 }
 
+/// Lowercase hex representation of bytes.
+///
+/// # Examples
+///
+/// ```
+/// use xtask::core_dist::to_hex;
+///
+/// assert_eq!(to_hex(&[]), "");
+/// assert_eq!(to_hex(&[0x00, 0x0f, 0xab]), "000fab");
+/// ```
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Relative path as URL path (`/` separated, without leading `/`).
+///
+/// # Examples
+///
+/// ```
+/// use std::path::Path;
+/// use xtask::core_dist::url_path;
+///
+/// assert_eq!(url_path(Path::new("fonts/a.otf")), "fonts/a.otf");
+/// ```
+pub fn url_path(relative_path: &Path) -> String {
+    relative_path
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Value of `--name value` argument passed after task name.
+pub fn task_option(name: &str) -> Option<String> {
+    let mut args = env::args().skip(2);
+    while let Some(arg) = args.next() {
+        if arg == name {
+            return args.next();
+        }
+    }
+    None
+}
+
+/// Whether `--name` flag was passed after task name.
+pub fn task_flag(name: &str) -> bool {
+    env::args().skip(2).any(|arg| arg == name)
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// Thread Pool Primitives
+////////////////////////////////////////////////////////////////////////////////
+
+/// Spread tasks sent by `feed` (each for file at given path) over thread pool
+/// (thread per available core, at most thread per task) pulling them from
+/// shared queue, each task is handled by `worker`.
+/// Failures are handled according to `policy` (fail-fast stops handing out
+/// remaining tasks) and reported by file path.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::Error;
+/// use std::path::PathBuf;
+/// use xtask::core_dist::{FailurePolicy, OK, task_swarm};
+///
+/// let worker = |n: &u32| if n % 2 == 0 { OK } else { Err(Error::other("odd")) };
+/// let feed = |send_task: &mut dyn FnMut(PathBuf, u32)| {
+///     (1..=4).for_each(|n| send_task(PathBuf::from(format!("{}.txt", n)), n));
+///     OK
+/// };
+///
+/// let report = task_swarm(FailurePolicy::BestEffort, worker, feed).unwrap();
+/// assert_eq!(report.succeeded, [PathBuf::from("2.txt"), PathBuf::from("4.txt")]);
+/// assert_eq!(report.failed.len(), 2);
+///
+/// let error = task_swarm(FailurePolicy::CollectAndFail, worker, feed)
+///     .unwrap_err()
+///     .to_string();
+/// assert_eq!(error, "2 of 4 files failed: 1.txt: odd; 3.txt: odd");
+/// ```
+pub fn task_swarm<T, W, F>(
+    policy: FailurePolicy,
+    worker: W,
+    feed: F,
+) -> Result<FileTreeReport>
+where
+    T: Send + Sync,
+    W: Fn(&T) -> TaskResult + Sync,
+    F: FnOnce(&mut dyn FnMut(PathBuf, T)) -> TaskResult,
+{
+    let mut tasks = vec![];
+    feed(&mut |path, task| tasks.push((path, task)))?;
+
+    let available_parallelism = thread::available_parallelism()?.get();
+    assert!(available_parallelism > 0, "0 parallelism?!");
+
+    let next_task = AtomicUsize::new(0);
+    let report = Mutex::new(FileTreeReport::default());
+    let fail_fast_error = Mutex::new(None);
+    thread::scope(|s| {
+        for _ in 0..available_parallelism.min(tasks.len()) {
+            s.spawn(|| {
+                while let Some((path, task)) =
+                    tasks.get(next_task.fetch_add(1, Ordering::Relaxed))
+                {
+                    let result = worker(task).map(|()| true);
+                    let recorded = report
+                        .lock()
+                        .expect("poisoned report")
+                        .record(path.clone(), result, policy);
+                    if let Err(e) = recorded {
+                        // fail-fast: stop handing out remaining tasks
+                        next_task.store(tasks.len(), Ordering::Relaxed);
+                        fail_fast_error
+                            .lock()
+                            .expect("poisoned error")
+                            .get_or_insert(e);
+                    }
+                }
+            });
+        }
+    });
+
+    if let Some(e) = fail_fast_error.into_inner().expect("poisoned error") {
+        return Err(e);
+    }
+    let mut report = report.into_inner().expect("poisoned report");
+    // workers finish in any order
+    report.succeeded.sort();
+    report.failed.sort_by(|(a, _), (b, _)| a.cmp(b));
+    report.finish(policy)
+}
+
 ////////////////////////////////////////////////////////////////////////////////
 // Shell Manipulation Primitives
 ////////////////////////////////////////////////////////////////////////////////
@@ -265,13 +530,7 @@ pub fn shell_log_piped(
     args: &[&str],
     env: &[(&str, &str)],
 ) -> TaskResult {
-    let status = shell(cmd, args, env, false)?;
-
-    if !status.success() {
-        Err(Error::from(ErrorKind::Interrupted))
-    } else {
-        OK
-    }
+    shell_log_piped_as(cmd, cmd, args, env)
 }
 
 /// Evaluate shell command and pipe output directly to stdout(err).
@@ -281,24 +540,106 @@ pub fn transparent_shell(
     args: &[&str],
     env: &[(&str, &str)],
 ) -> Result<ExitStatus> {
-    shell(cmd, args, env, true)
+    shell(cmd, cmd, args, env, true)
 }
 
 /// Evaluate cargo command and pipe output to logger (threaded).
 /// Returns ErrorKind::Interrupted if cargo was not successful.
 pub fn cargo(args: &[&str]) -> TaskResult {
+    cargo_with_env(args, &[])
+}
+
+/// Same as `cargo`, but with additional environment variables.
+pub fn cargo_with_env(args: &[&str], env: &[(&str, &str)]) -> TaskResult {
     let cargo = env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
-    shell_log_piped(&cargo, args, &[])
+    // `CARGO` is full path: keep `[cargo stderr]` (problem matchers of
+    // .vscode/tasks.json)
+    shell_log_piped_as("cargo", &cargo, args, env)
 }
 
 ////////////////////////////////////////////////////////////////////////////////
 // Private
 ////////////////////////////////////////////////////////////////////////////////
 
-/// Evaluate shell command and pipe output either to logger (threaded)
-/// or directly to stdout(err).
+/// Apply `f` to every file of `root` (`Ok(false)` if file is skipped)
+/// respecting failure policy.
+fn walk_file_tree<F>(
+    root: &Path,
+    policy: FailurePolicy,
+    mut f: F,
+) -> Result<FileTreeReport>
+where
+    F: FnMut(&Path) -> Result<bool>,
+{
+    let mut report = FileTreeReport::default();
+    // root should be listable whatever policy is
+    let mut remaining_listings = vec![fs::read_dir(root)?];
+
+    // DFS loop (stack top is listing of deepest directory)
+    while let Some(listing) = remaining_listings.last_mut() {
+        let Some(entry) = listing.next() else {
+            remaining_listings.pop();
+            continue;
+        };
+
+        let entry = match entry.and_then(|entry| {
+            entry.file_type().map(|file_type| (entry.path(), file_type))
+        }) {
+            Ok(entry) => entry,
+            Err(e) => {
+                report.record(root.to_owned(), Err(e), policy)?;
+                continue;
+            }
+        };
+
+        match entry {
+            (path, file_type) if file_type.is_file() => {
+                let relative_path = path
+                    .strip_prefix(root)
+                    .expect("listing entry should be taken from child of `base_dir` (or itself)")
+                    .to_owned();
+                let result = f(&relative_path);
+                report.record(relative_path, result, policy)?;
+            }
+            (path, file_type) if file_type.is_dir() => {
+                log::debug!(
+                    "[xtask] processing recursively dir {}",
+                    path.display()
+                );
+                match fs::read_dir(&path) {
+                    Ok(listing) => remaining_listings.push(listing),
+                    Err(e) => report.record(path, Err(e), policy)?,
+                }
+            }
+            // if something else -> skip
+            _ => {}
+        }
+    }
+
+    report.finish(policy)
+}
+
+/// Same as `shell_log_piped`, but output is logged under `name`.
+fn shell_log_piped_as(
+    name: &str,
+    cmd: &str,
+    args: &[&str],
+    env: &[(&str, &str)],
+) -> TaskResult {
+    let status = shell(name, cmd, args, env, false)?;
+
+    if !status.success() {
+        Err(Error::from(ErrorKind::Interrupted))
+    } else {
+        OK
+    }
+}
+
+/// Evaluate shell command and pipe output either to logger (threaded,
+/// lines are prefixed with `name`) or directly to stdout(err).
 /// Returns ExitStatus as success.
 fn shell(
+    name: &str,
     cmd: &str,
     args: &[&str],
     env: &[(&str, &str)],
@@ -350,8 +691,8 @@ fn shell(
         .expect("failed to acquire piped stderr");
 
     thread::scope(|s| {
-        let h = s.spawn(|| log_pipe(&cmd, &mut stderr, false));
-        if let Err(e) = log_pipe(&cmd, &mut stdout, true) {
+        let h = s.spawn(|| log_pipe(name, &mut stderr, false));
+        if let Err(e) = log_pipe(name, &mut stdout, true) {
             log::error!(
                 "[shell] error occurred while piping shell stdout: {}",
                 e
@@ -427,65 +768,3 @@ fn log_pipe(
             line_buffer.split_at_mut(slice_index);
     }
 }
-
-/// Pop files in `full_path` into `f` and pass dirs through (lazy).
-/// `f` receives path relative to `base_path`.
-/// `full_path` should be child of `base_path`.
-fn pop_files<'a, 'b, F>(
-    base_path: &'a Path,
-    full_path: &'b Path,
-    f: &'a RefCell<F>,
-) -> Result<impl Iterator<Item = PathBuf> + 'a>
-where
-    F: FnMut(&Path) -> () + 'a,
-{
-    assert!(
-        full_path.strip_prefix(base_path).is_ok(),
-        "full_path should be child of base_path"
-    );
-
-    let listing = fs::read_dir(full_path)?;
-
-    let lazy_map = listing.filter_map(move |listing_result| {
-        match listing_result {
-            Ok(entry) => match entry.file_type() {
-                // if file -> callback and skip
-                Ok(fd) if fd.is_file() => {
-                    // calc relative path
-                    let relative_path = entry
-                        .path()
-                        .strip_prefix(base_path)
-                        .expect("listing entry should be taken from child of `base_dir` (or itself)")
-                        .to_owned();
-                    // Panic Safety: the only place where we do the borrow
-                    f.borrow_mut()(&relative_path);
-                    // filter out
-                    None
-                }
-                // if dir -> pass further as path
-                Ok(fd) if fd.is_dir() => Some(entry.path()),
-                // if something else -> skip
-                Ok(_) => None,
-                // if error -> log and skip
-                Err(e) => {
-                    log::error!(
-                        "[xtask] Error while processing {}: {}",
-                        entry.file_name().display(),
-                        e
-                    );
-                    None
-                }
-            },
-            // if error -> log and skip
-            Err(e) => {
-                log::error!(
-                    "[xtask] Error while processing files recursively: {}",
-                    e
-                );
-                None
-            }
-        }
-    });
-
-    Ok(lazy_map)
-}
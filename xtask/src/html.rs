@@ -0,0 +1,293 @@
+use std::ops::Range;
+
+////////////////////////////////////////////////////////////////////////////////
+// Tokens
+////////////////////////////////////////////////////////////////////////////////
+
+/// Attribute of start tag (`value` is raw, entities are not decoded)
+#[derive(Clone, Debug, PartialEq)]
+pub struct Attribute<'a> {
+    pub name: String,
+    pub value: Option<&'a str>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TokenKind<'a> {
+    Doctype,
+    Comment(&'a str),
+    Text(&'a str),
+    StartTag {
+        /// lowercase
+        name: String,
+        attributes: Vec<Attribute<'a>>,
+        self_closing: bool,
+    },
+    EndTag {
+        /// lowercase
+        name: String,
+    },
+    /// Content of `<script>` and `<style>` (never split into tags)
+    RawText(&'a str),
+}
+
+/// Token with its byte range in source
+#[derive(Clone, Debug, PartialEq)]
+pub struct Token<'a> {
+    pub kind: TokenKind<'a>,
+    pub span: Range<usize>,
+}
+
+impl<'a> Token<'a> {
+    /// Name of start or end tag
+    pub fn tag_name(&self) -> Option<&str> {
+        match &self.kind {
+            TokenKind::StartTag { name, .. } | TokenKind::EndTag { name } => {
+                Some(name)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn is_start_tag(&self, tag: &str) -> bool {
+        matches!(&self.kind, TokenKind::StartTag { name, .. } if name == tag)
+    }
+
+    /// Value of start tag attribute (`Some("")` for attribute without value)
+    pub fn attribute(&self, attribute: &str) -> Option<&'a str> {
+        match &self.kind {
+            TokenKind::StartTag { attributes, .. } => attributes
+                .iter()
+                .find(|known| known.name == attribute)
+                .map(|known| known.value.unwrap_or_default()),
+            _ => None,
+        }
+    }
+
+    /// Byte offset right before `>` (or `/>`) of start tag, where new
+    /// attributes can be inserted
+    pub fn attribute_insert_offset(&self, html: &str) -> usize {
+        let tag = &html[self.span.clone()];
+        let tag = tag.strip_suffix('>').unwrap_or(tag);
+        let tag = tag.strip_suffix('/').unwrap_or(tag);
+        self.span.start + tag.len()
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// Tokenizer
+////////////////////////////////////////////////////////////////////////////////
+
+const RAW_TEXT_ELEMENTS: &[&str] = &["script", "style"];
+
+/// Split HTML into tokens. Good enough for front-page sources and their
+/// minified output: never fails, malformed markup ends up as text.
+///
+/// # Examples
+///
+/// ```
+/// use xtask::html::{TokenKind, tokenize};
+///
+/// let html = "<!doctype html><p class=a id='b'>x<br/><script>1<2</script>";
+/// let tokens = tokenize(html);
+///
+/// assert_eq!(tokens[0].kind, TokenKind::Doctype);
+/// assert_eq!(tokens[1].attribute("class"), Some("a"));
+/// assert_eq!(tokens[1].attribute("id"), Some("b"));
+/// assert_eq!(tokens[2].kind, TokenKind::Text("x"));
+/// assert!(tokens[3].is_start_tag("br"));
+/// assert_eq!(tokens[5].kind, TokenKind::RawText("1<2"));
+/// assert_eq!(tokens[6].tag_name(), Some("script"));
+/// assert_eq!(&html[tokens[1].span.clone()], "<p class=a id='b'>");
+/// ```
+pub fn tokenize(html: &str) -> Vec<Token<'_>> {
+    let mut tokens = vec![];
+    let mut position = 0;
+
+    while position < html.len() {
+        let rest = &html[position..];
+        let (kind, length) = if let Some(comment) = rest.strip_prefix("<!--") {
+            match comment.find("-->") {
+                Some(end) => (TokenKind::Comment(&comment[..end]), end + 7),
+                None => (TokenKind::Comment(comment), rest.len()),
+            }
+        } else if rest.starts_with("<!") {
+            (
+                TokenKind::Doctype,
+                rest.find('>').map_or(rest.len(), |i| i + 1),
+            )
+        } else if let Some(after) = rest.strip_prefix("</")
+            && after.starts_with(|c: char| c.is_ascii_alphabetic())
+        {
+            let name_length = tag_name_length(after);
+            let length = rest.find('>').map_or(rest.len(), |i| i + 1);
+            let name = after[..name_length].to_ascii_lowercase();
+            (TokenKind::EndTag { name }, length)
+        } else if let Some(after) = rest.strip_prefix('<')
+            && after.starts_with(|c: char| c.is_ascii_alphabetic())
+        {
+            start_tag(rest)
+        } else {
+            // at least one char, even if it is stray `<`
+            let first = rest.chars().next().map_or(1, char::len_utf8);
+            let end = rest[first..].find('<').map_or(rest.len(), |i| i + first);
+            (TokenKind::Text(&rest[..end]), end)
+        };
+
+        let raw_text_element = match &kind {
+            TokenKind::StartTag {
+                name, self_closing, ..
+            } if !self_closing
+                && RAW_TEXT_ELEMENTS.contains(&name.as_str()) =>
+            {
+                Some(format!("</{}", name))
+            }
+            _ => None,
+        };
+
+        tokens.push(Token {
+            kind,
+            span: position..position + length,
+        });
+        position += length;
+
+        if let Some(end_tag) = raw_text_element {
+            let rest = &html[position..];
+            let length =
+                find_ignore_ascii_case(rest, &end_tag).unwrap_or(rest.len());
+            if length > 0 {
+                tokens.push(Token {
+                    kind: TokenKind::RawText(&rest[..length]),
+                    span: position..position + length,
+                });
+                position += length;
+            }
+        }
+    }
+
+    tokens
+}
+
+/// 1-based line number of byte offset
+///
+/// # Examples
+///
+/// ```
+/// use xtask::html::line_number;
+///
+/// assert_eq!(line_number("a\nb\nc", 0), 1);
+/// assert_eq!(line_number("a\nb\nc", 2), 2);
+/// assert_eq!(line_number("a\nb\nc", 4), 3);
+/// ```
+pub fn line_number(html: &str, offset: usize) -> usize {
+    html[..offset.min(html.len())].matches('\n').count() + 1
+}
+
+/// Parse start tag at beginning of `rest` (starts with `<` and letter)
+fn start_tag(rest: &str) -> (TokenKind<'_>, usize) {
+    let bytes = rest.as_bytes();
+    let name_length = tag_name_length(&rest[1..]);
+    let name = rest[1..1 + name_length].to_ascii_lowercase();
+    let mut attributes = vec![];
+    let mut position = 1 + name_length;
+
+    loop {
+        while position < bytes.len() && bytes[position].is_ascii_whitespace() {
+            position += 1;
+        }
+        let (self_closing, length) = match bytes.get(position) {
+            None => (false, rest.len()),
+            Some(b'>') => (false, position + 1),
+            Some(b'/') if bytes.get(position + 1) == Some(&b'>') => {
+                (true, position + 2)
+            }
+            Some(b'/') => {
+                position += 1;
+                continue;
+            }
+            Some(_) => {
+                let attribute;
+                (attribute, position) = start_tag_attribute(rest, position);
+                attributes.push(attribute);
+                continue;
+            }
+        };
+
+        let kind = TokenKind::StartTag {
+            name,
+            attributes,
+            self_closing,
+        };
+        return (kind, length);
+    }
+}
+
+/// Parse attribute at `position` of start tag, returns it with end position
+fn start_tag_attribute(
+    rest: &str,
+    mut position: usize,
+) -> (Attribute<'_>, usize) {
+    let bytes = rest.as_bytes();
+    let is_space = |byte: u8| byte.is_ascii_whitespace();
+
+    let name_start = position;
+    while position < bytes.len()
+        && !is_space(bytes[position])
+        && !matches!(bytes[position], b'=' | b'>')
+        && !rest[position..].starts_with("/>")
+    {
+        position += 1;
+    }
+    let name = rest[name_start..position].to_ascii_lowercase();
+
+    let mut value_start = position;
+    while value_start < bytes.len() && is_space(bytes[value_start]) {
+        value_start += 1;
+    }
+    if bytes.get(value_start) != Some(&b'=') {
+        return (Attribute { name, value: None }, position);
+    }
+    position = value_start + 1;
+    while position < bytes.len() && is_space(bytes[position]) {
+        position += 1;
+    }
+
+    let value = match bytes.get(position) {
+        Some(&quote @ (b'"' | b'\'')) => {
+            let start = position + 1;
+            let end = rest[start..]
+                .find(quote as char)
+                .map_or(rest.len(), |i| start + i);
+            position = (end + 1).min(rest.len());
+            &rest[start..end]
+        }
+        _ => {
+            let start = position;
+            while position < bytes.len()
+                && !is_space(bytes[position])
+                && bytes[position] != b'>'
+            {
+                position += 1;
+            }
+            &rest[start..position]
+        }
+    };
+
+    (
+        Attribute {
+            name,
+            value: Some(value),
+        },
+        position,
+    )
+}
+
+fn tag_name_length(text: &str) -> usize {
+    text.find(|c: char| c.is_ascii_whitespace() || c == '>' || c == '/')
+        .unwrap_or(text.len())
+}
+
+fn find_ignore_ascii_case(text: &str, needle: &str) -> Option<usize> {
+    text.as_bytes()
+        .windows(needle.len())
+        .position(|window| window.eq_ignore_ascii_case(needle.as_bytes()))
+}
@@ -1,14 +1,42 @@
 pub mod core_dist;
+pub mod html;
 pub mod paths;
+pub mod web_app;
+pub mod web_build_manifest;
+pub mod web_compress;
+pub mod web_deploy;
 pub mod web_dist;
+pub mod web_error_pages;
+pub mod web_fingerprint;
+pub mod web_font_subset;
+pub mod web_headers;
+pub mod web_integrity;
+pub mod web_links;
+pub mod web_lint;
+pub mod web_nginx;
+pub mod web_server;
+pub mod web_service_worker;
+pub mod web_size;
+pub mod web_template;
+pub mod web_wasm_bindgen;
+pub mod web_wasm_loader;
+pub mod web_wasm_opt;
+pub mod web_wasm_report;
+pub mod web_watch;
 
 use core_dist::{OK, TaskResult, make_each_directory};
 use std::{collections::HashMap, env};
-use web_dist::{
-    build_web_distribution, build_web_distribution_dev,
-    prepare_serve_web_distribution, prepare_serve_web_distribution_dev,
-    serve_web_distribution, serve_web_distribution_dev,
-};
+use web_build_manifest::verify_reproducible;
+use web_deploy::{deploy, package_web_distribution, rollback};
+use web_dist::{build_web_distribution, build_web_distribution_dev};
+use web_headers::generate_nginx_headers;
+use web_links::check_links;
+use web_lint::lint_front_page;
+use web_nginx::generate_nginx_conf;
+use web_server::{serve_web_distribution, serve_web_distribution_dev};
+use web_size::size_report;
+use web_wasm_report::wasm_report;
+use web_watch::{watch_web_distribution, watch_web_distribution_dev};
 
 ////////////////////////////////////////////////////////////////////////////////
 // CLI Task Bindings
@@ -28,7 +56,52 @@ static TASKS_LIST: &[TasksListEntry] = &[
     (
         "serve-web-dist",
         serve_web_distribution,
-        "serve distribution for web on http://127.0.0.1:8080 (via simple-http-server)",
+        "serve distribution for web on http://127.0.0.1:8080",
+    ),
+    (
+        "watch-web-dist",
+        watch_web_distribution,
+        "build distribution for web and rebuild changed parts on file changes",
+    ),
+    (
+        "size-report",
+        size_report,
+        "report sizes of web distribution, compare with previous report and budgets",
+    ),
+    (
+        "wasm-report",
+        wasm_report,
+        "report wasm code size by crate and function (--names, --wasm, --top)",
+    ),
+    (
+        "lint-front-page",
+        lint_front_page,
+        "lint front-page html (validity, lang, title, alt, contrast, keyboard access)",
+    ),
+    (
+        "check-links",
+        check_links,
+        "check references of built web distribution pages to its files (--dev)",
+    ),
+    (
+        "verify-reproducible",
+        verify_reproducible,
+        "build web distribution twice from scratch and compare build manifests (--incremental)",
+    ),
+    (
+        "package-web-dist",
+        package_web_distribution,
+        "pack built web distribution into versioned tarball in target/packages",
+    ),
+    (
+        "deploy",
+        deploy,
+        "unpack package into releases of --target and switch current to it (--package, --keep)",
+    ),
+    (
+        "rollback",
+        rollback,
+        "switch current of --target back to previous release",
     ),
     (
         "build-windows-dist",
@@ -48,7 +121,12 @@ static TASKS_LIST: &[TasksListEntry] = &[
     (
         "serve-web-dist-dev",
         serve_web_distribution_dev,
-        "serve developer distribution for web on http://127.0.0.1:8080 (via simple-http-server)",
+        "serve developer distribution for web on http://127.0.0.1:8080 (with live reload)",
+    ),
+    (
+        "watch-web-dist-dev",
+        watch_web_distribution_dev,
+        "build developer distribution for web and rebuild changed parts on file changes",
     ),
     (
         "build-windows-dist-dev",
@@ -60,16 +138,15 @@ static TASKS_LIST: &[TasksListEntry] = &[
         todo_placeholder,
         "build developer distribution for android (arm64 apk)",
     ),
-    // For VS launch "Serve (Dev) Web Package"
     (
-        "prepare-serve-web-dist",
-        prepare_serve_web_distribution,
-        "build distribution for web and install simple-http-server",
+        "gen-nginx-headers",
+        generate_nginx_headers,
+        "generate nginx config snippets applying deploy/headers.conf",
     ),
     (
-        "prepare-serve-web-dist-dev",
-        prepare_serve_web_distribution_dev,
-        "build developer distribution for web and install simple-http-server",
+        "gen-nginx-conf",
+        generate_nginx_conf,
+        "generate nginx config serving web distribution (--server-name, --root, --listen, --no-brotli)",
     ),
     ("help", print_help, "print help (this) message"),
 ];
@@ -6,6 +6,22 @@ pub const WEB_DIST_DEV_SUBDIRECTORY: &str = "web-dist-dev";
 
 pub const WASM_PKG_SUBDIRECTORY: &str = "shards-browser-pkg";
 pub const WASM_PKG_DEV_SUBDIRECTORY: &str = "shards-browser-dev-pkg";
+pub const WASM_PKG_NAMES_SUBDIRECTORY: &str = "shards-browser-names-pkg";
+
+/// Expanded front-page and rendered error pages before minification (release)
+pub const FRONT_PAGE_EXPANDED_SUBDIRECTORY: &str = "front-page-expanded";
+pub const ERROR_PAGES_SUBDIRECTORY: &str = "error-pages";
+
+/// Versioned tarballs of release distribution (`package-web-dist`)
+pub const PACKAGES_SUBDIRECTORY: &str = "packages";
+
+pub const DEPLOY_DIRECTORY: &str = "deploy";
+
+pub const HEADER_RULES_FILE: &str = "headers.conf";
+
+pub const SIZE_BUDGETS_FILE: &str = "size-budgets.conf";
+
+pub const FONT_SUBSET_FILE: &str = "font-subset.conf";
 
 // Calculated
 
@@ -21,3 +37,15 @@ pub static PROJECT_ROOT: LazyLock<&Path> = LazyLock::new(|| {
 
 pub static BUILD_PATH: LazyLock<PathBuf> =
     LazyLock::new(|| PROJECT_ROOT.join(BUILD_DIRECTORY));
+
+pub static HEADER_RULES_PATH: LazyLock<PathBuf> = LazyLock::new(|| {
+    PROJECT_ROOT.join(DEPLOY_DIRECTORY).join(HEADER_RULES_FILE)
+});
+
+pub static SIZE_BUDGETS_PATH: LazyLock<PathBuf> = LazyLock::new(|| {
+    PROJECT_ROOT.join(DEPLOY_DIRECTORY).join(SIZE_BUDGETS_FILE)
+});
+
+pub static FONT_SUBSET_PATH: LazyLock<PathBuf> = LazyLock::new(|| {
+    PROJECT_ROOT.join(DEPLOY_DIRECTORY).join(FONT_SUBSET_FILE)
+});
@@ -0,0 +1,387 @@
+use std::fs;
+use std::io::{Error, ErrorKind, Result};
+use std::path::Path;
+
+use image::codecs::ico::{IcoEncoder, IcoFrame};
+use image::codecs::png::{
+    CompressionType, FilterType as PngFilterType, PngEncoder,
+};
+use image::imageops::{self, FilterType};
+use image::{ExtendedColorType, Rgba, RgbaImage};
+use resvg::{tiny_skia, usvg};
+use serde::{Deserialize, Serialize};
+
+use crate::core_dist::{OK, TaskResult};
+use crate::paths;
+
+////////////////////////////////////////////////////////////////////////////////
+// Web App Settings
+////////////////////////////////////////////////////////////////////////////////
+
+/// Settings are read from `[package.metadata.xtask.web-app]` of
+/// shards-browser manifest
+const SHARDS_BROWSER_MANIFEST: &str = "shards-browser/Cargo.toml";
+
+pub const WEB_APP_MANIFEST_FILE: &str = "manifest.webmanifest";
+
+/// Sizes packed into `favicon.ico`
+const FAVICON_FILE: &str = "favicon.ico";
+const FAVICON_SIZES: &[u32] = &[16, 32, 48];
+
+const APPLE_TOUCH_ICON_FILE: &str = "apple-touch-icon.png";
+const APPLE_TOUCH_ICON_SIZE: u32 = 180;
+
+/// Icons listed in web app manifest (`icon-<size>.png`)
+const MANIFEST_ICON_SIZES: &[u32] = &[192, 512];
+
+////////////////////////////////////////////////////////////////////////////////
+// Web App
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebApp {
+    pub name: String,
+    pub short_name: String,
+    pub description: String,
+    pub start_url: String,
+    pub display: String,
+    pub theme_color: String,
+    pub background_color: String,
+    /// Square SVG or raster image (relative to front-page), skipped in
+    /// manifest
+    #[serde(skip_serializing)]
+    pub icon: String,
+}
+
+#[derive(Serialize)]
+struct ManifestIcon {
+    src: String,
+    sizes: String,
+    #[serde(rename = "type")]
+    mime_type: &'static str,
+}
+
+#[derive(Serialize)]
+struct Manifest<'a> {
+    #[serde(flatten)]
+    app: &'a WebApp,
+    icons: Vec<ManifestIcon>,
+}
+
+impl WebApp {
+    /// Settings of shards-browser manifest
+    pub fn load() -> Result<Self> {
+        let manifest_path = paths::PROJECT_ROOT.join(SHARDS_BROWSER_MANIFEST);
+        let manifest = toml::Value::Table(
+            fs::read_to_string(&manifest_path)?
+                .parse()
+                .map_err(|e| Error::new(ErrorKind::InvalidData, e))?,
+        );
+        let invalid = |message: String| {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!("{}: {}", manifest_path.display(), message),
+            )
+        };
+
+        let settings = ["package", "metadata", "xtask", "web-app"]
+            .iter()
+            .try_fold(&manifest, |value, key| value.get(key))
+            .ok_or_else(|| {
+                invalid("no [package.metadata.xtask.web-app]".to_owned())
+            })?;
+        let app: WebApp = settings
+            .clone()
+            .try_into()
+            .map_err(|e| invalid(format!("{}", e)))?;
+
+        for color in [&app.theme_color, &app.background_color] {
+            if !is_hex_color(color) {
+                return Err(invalid(format!("{} is not #rrggbb color", color)));
+            }
+        }
+        Ok(app)
+    }
+
+    /// `<meta>` and `<link>` tags of page `<head>`: theme color, icons and
+    /// web app manifest
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xtask::web_app::WebApp;
+    ///
+    /// let app = WebApp {
+    ///     name: "Shards Browser".to_owned(),
+    ///     short_name: "Shards".to_owned(),
+    ///     description: String::new(),
+    ///     start_url: "/".to_owned(),
+    ///     display: "standalone".to_owned(),
+    ///     theme_color: "#FFFFFF".to_owned(),
+    ///     background_color: "#000000".to_owned(),
+    ///     icon: "icon.svg".to_owned(),
+    /// };
+    /// let tags = app.head_tags();
+    ///
+    /// assert!(tags.contains("<meta name=\"theme-color\" content=\"#FFFFFF\">"));
+    /// assert!(tags.contains("<link rel=\"icon\" href=\"/icon.svg\" type=\"image/svg+xml\">"));
+    /// assert!(tags.contains("<link rel=\"manifest\" href=\"/manifest.webmanifest\">"));
+    /// ```
+    pub fn head_tags(&self) -> String {
+        let mut tags = vec![
+            format!(
+                "<meta name=\"theme-color\" content=\"{}\">",
+                self.theme_color
+            ),
+            format!(
+                "<link rel=\"icon\" href=\"/{}\" sizes=\"{}\">",
+                FAVICON_FILE,
+                FAVICON_SIZES
+                    .iter()
+                    .map(|size| format!("{0}x{0}", size))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            ),
+        ];
+        if self.is_svg_icon() {
+            tags.push(format!(
+                "<link rel=\"icon\" href=\"/{}\" type=\"image/svg+xml\">",
+                self.icon
+            ));
+        }
+        tags.push(format!(
+            "<link rel=\"apple-touch-icon\" href=\"/{}\">",
+            APPLE_TOUCH_ICON_FILE
+        ));
+        tags.push(format!(
+            "<link rel=\"manifest\" href=\"/{}\">",
+            WEB_APP_MANIFEST_FILE
+        ));
+
+        tags.join("\n        ")
+    }
+
+    /// Whether front-page path is icon source
+    pub fn is_icon_source(&self, relative_path: &Path) -> bool {
+        relative_path == Path::new(&self.icon)
+    }
+
+    fn is_svg_icon(&self) -> bool {
+        self.icon.to_ascii_lowercase().ends_with(".svg")
+    }
+
+    fn manifest_json(&self) -> Result<String> {
+        let manifest = Manifest {
+            app: self,
+            icons: MANIFEST_ICON_SIZES
+                .iter()
+                .map(|size| ManifestIcon {
+                    src: format!("/{}", manifest_icon_file(*size)),
+                    sizes: format!("{0}x{0}", size),
+                    mime_type: "image/png",
+                })
+                .collect(),
+        };
+        serde_json::to_string_pretty(&manifest).map_err(Error::other)
+    }
+}
+
+/// Render icons of web app from its source and write them along with web app
+/// manifest into `web_dist_path`
+pub fn generate_web_app_files(
+    web_dist_path: &Path,
+    front_page_path: &Path,
+) -> TaskResult {
+    let app = WebApp::load()?;
+    let source_path = front_page_path.join(&app.icon);
+    log::info!(
+        "[xtask] Rendering web app icons from {}",
+        source_path.display()
+    );
+    let source = IconSource::load(&source_path)?;
+    if let IconSource::Raster(image) = &source {
+        let largest = MANIFEST_ICON_SIZES.iter().max().copied().unwrap_or(0);
+        if image.width() < largest {
+            // upscaled app icons would be blurred or pixelated
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "{} is {}px, raster icon source should be at least {}px \
+                     (or SVG)",
+                    source_path.display(),
+                    image.width(),
+                    largest
+                ),
+            ));
+        }
+    }
+
+    let mut frames = vec![];
+    for size in FAVICON_SIZES {
+        frames.push(
+            IcoFrame::as_png(
+                source.render(*size).as_raw(),
+                *size,
+                *size,
+                ExtendedColorType::Rgba8,
+            )
+            .map_err(Error::other)?,
+        );
+    }
+    let mut favicon = vec![];
+    IcoEncoder::new(&mut favicon)
+        .encode_images(&frames)
+        .map_err(Error::other)?;
+    fs::write(web_dist_path.join(FAVICON_FILE), favicon)?;
+
+    let mut pngs =
+        vec![(APPLE_TOUCH_ICON_FILE.to_owned(), APPLE_TOUCH_ICON_SIZE)];
+    pngs.extend(
+        MANIFEST_ICON_SIZES
+            .iter()
+            .map(|size| (manifest_icon_file(*size), *size)),
+    );
+    for (file, size) in pngs {
+        let mut png = vec![];
+        source
+            .render(size)
+            .write_with_encoder(PngEncoder::new_with_quality(
+                &mut png,
+                CompressionType::Best,
+                PngFilterType::Adaptive,
+            ))
+            .map_err(Error::other)?;
+        fs::write(web_dist_path.join(file), png)?;
+    }
+
+    let manifest_path = web_dist_path.join(WEB_APP_MANIFEST_FILE);
+    log::info!("[xtask] Writing {}", manifest_path.display());
+    fs::write(manifest_path, app.manifest_json()?)?;
+
+    OK
+}
+
+fn manifest_icon_file(size: u32) -> String {
+    format!("icon-{}.png", size)
+}
+
+fn is_hex_color(color: &str) -> bool {
+    color.len() == 7
+        && color.starts_with('#')
+        && color[1..].chars().all(|c| c.is_ascii_hexdigit())
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// Icon Rendering
+////////////////////////////////////////////////////////////////////////////////
+
+/// Square icon image rendered at any size
+pub enum IconSource {
+    Svg(Box<usvg::Tree>),
+    Raster(RgbaImage),
+}
+
+impl IconSource {
+    /// SVG (by extension) or any raster image (PNG, ICO)
+    pub fn load(path: &Path) -> Result<Self> {
+        let data = fs::read(path)?;
+        let invalid = |message: String| {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!("{}: {}", path.display(), message),
+            )
+        };
+
+        let is_svg = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("svg"));
+        let (source, width, height) = if is_svg {
+            let tree = usvg::Tree::from_data(&data, &usvg::Options::default())
+                .map_err(|e| invalid(e.to_string()))?;
+            let size = tree.size();
+            (IconSource::Svg(Box::new(tree)), size.width(), size.height())
+        } else {
+            let image = image::load_from_memory(&data)
+                .map_err(|e| invalid(e.to_string()))?
+                .into_rgba8();
+            let (width, height) = image.dimensions();
+            (IconSource::Raster(image), width as f32, height as f32)
+        };
+
+        if width != height {
+            return Err(invalid(format!(
+                "icon should be square, not {}x{}",
+                width, height
+            )));
+        }
+        Ok(source)
+    }
+
+    pub fn render(&self, size: u32) -> RgbaImage {
+        match self {
+            IconSource::Svg(tree) => {
+                let mut pixmap = tiny_skia::Pixmap::new(size, size)
+                    .expect("icon size is not zero");
+                let scale = size as f32 / tree.size().width();
+                resvg::render(
+                    tree,
+                    tiny_skia::Transform::from_scale(scale, scale),
+                    &mut pixmap.as_mut(),
+                );
+
+                let mut image = RgbaImage::new(size, size);
+                for (pixel, color) in image.pixels_mut().zip(pixmap.pixels()) {
+                    let color = color.demultiply();
+                    *pixel = Rgba([
+                        color.red(),
+                        color.green(),
+                        color.blue(),
+                        color.alpha(),
+                    ]);
+                }
+                image
+            }
+            IconSource::Raster(image) => {
+                imageops::resize(image, size, size, FilterType::Lanczos3)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checked_in_web_app_generates_icons_and_manifest() {
+        let dir = tempfile::tempdir().unwrap();
+        generate_web_app_files(
+            dir.path(),
+            &paths::PROJECT_ROOT.join("front-page"),
+        )
+        .unwrap();
+
+        let favicon = image::load_from_memory(
+            &fs::read(dir.path().join(FAVICON_FILE)).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(favicon.width(), 48);
+        let icon = image::load_from_memory(
+            &fs::read(dir.path().join("icon-512.png")).unwrap(),
+        )
+        .unwrap()
+        .into_rgba8();
+        assert_eq!(icon.dimensions(), (512, 512));
+
+        let manifest: serde_json::Value = serde_json::from_slice(
+            &fs::read(dir.path().join(WEB_APP_MANIFEST_FILE)).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(manifest["start_url"], "/");
+        assert!(manifest.get("icon").is_none());
+        for icon in manifest["icons"].as_array().unwrap() {
+            let src = icon["src"].as_str().unwrap();
+            assert!(dir.path().join(&src[1..]).is_file(), "{}", src);
+        }
+    }
+}
@@ -0,0 +1,318 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{Error, Result};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::core_dist::{
+    DistributionPath, FailurePolicy, OK, TaskResult, task_flag, to_hex,
+    url_path,
+};
+use crate::paths;
+use crate::web_dist::build_web_distribution_by_path;
+use crate::web_template::commit_hash;
+use crate::web_wasm_bindgen::{
+    WASM_VARIANTS, clean_wasm_build, locked_package_version,
+};
+use crate::web_wasm_opt::WasmProfile;
+
+////////////////////////////////////////////////////////////////////////////////
+// Manifest Settings
+////////////////////////////////////////////////////////////////////////////////
+
+/// Written into distribution root (not listed in itself)
+pub const BUILD_MANIFEST_FILE: &str = ".build-manifest.json";
+
+/// Sources distribution is built from (files and directories relative to
+/// project root)
+const INPUT_PATHS: &[&str] = &[
+    "Cargo.toml",
+    "Cargo.lock",
+    "deploy/font-subset.conf",
+    "front-page",
+    "shards-browser/Cargo.toml",
+    "shards-browser/src",
+    "shards-logger/Cargo.toml",
+    "shards-logger/src",
+];
+
+/// `(name, command, args)` printing version
+const TOOLS: &[(&str, &str, &[&str])] = &[
+    ("rustc", "rustc", &["--version"]),
+    ("cargo", "cargo", &["--version"]),
+    ("wasm-bindgen", "wasm-bindgen", &["--version"]),
+    ("wasm-opt", "wasm-opt", &["--version"]),
+];
+
+/// Library tools (versions are taken from Cargo.lock)
+const LIBRARY_TOOLS: &[&str] = &["minify-html"];
+
+/// `verify-reproducible` builds (relative to build directory)
+const VERIFY_BUILDS: [(&str, &str); 2] = [
+    ("web-dist-verify-a", "shards-browser-verify-a-pkg"),
+    ("web-dist-verify-b", "shards-browser-verify-b-pkg"),
+];
+
+////////////////////////////////////////////////////////////////////////////////
+// CLI Tasks
+////////////////////////////////////////////////////////////////////////////////
+
+/// Build release distribution twice into separate directories and compare
+/// their build manifests. Shards-browser wasm artifacts are removed before
+/// each build (reused artifacts would make second build a copy of first),
+/// `--incremental` keeps them.
+pub fn verify_reproducible() -> TaskResult {
+    let mut manifests = vec![];
+    for (web_dist_dir, wasm_pkg_dir) in VERIFY_BUILDS {
+        if !task_flag("--incremental") {
+            for variant in WASM_VARIANTS {
+                clean_wasm_build(WasmProfile::Release, variant)?;
+            }
+        }
+
+        let web_dist_path = paths::BUILD_PATH.join(web_dist_dir);
+        log::info!("[xtask] Verification build {}", web_dist_path.display());
+        build_web_distribution_by_path(
+            &web_dist_path,
+            &paths::BUILD_PATH.join(wasm_pkg_dir),
+            true,
+        )?;
+        manifests.push(BuildManifest::load(&web_dist_path)?);
+    }
+
+    let differences = manifests[0].diff(&manifests[1]);
+    if differences.is_empty() {
+        log::info!(
+            "[xtask] Reproducible: {} output files match",
+            manifests[0].outputs.len()
+        );
+        return OK;
+    }
+
+    for difference in differences.iter() {
+        log::error!("[xtask] {}", difference);
+    }
+    Err(Error::other(format!(
+        "builds differ ({} differences)",
+        differences.len()
+    )))
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// Build Manifest
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OutputFile {
+    pub size: u64,
+    pub sha256: String,
+}
+
+/// What distribution consists of and what it was built from and with
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct BuildManifest {
+    pub profile: String,
+    pub commit: String,
+    /// uncommitted changes in work tree
+    pub dirty: bool,
+    pub tools: BTreeMap<String, String>,
+    /// SHA-256 by path relative to project root
+    pub inputs: BTreeMap<String, String>,
+    /// by URL path relative to distribution root
+    pub outputs: BTreeMap<String, OutputFile>,
+}
+
+impl BuildManifest {
+    /// Describe distribution in `web_dist_path` (built just now)
+    pub fn collect(web_dist_path: &Path, release: bool) -> Result<Self> {
+        let mut manifest = BuildManifest {
+            profile: if release { "release" } else { "dev" }.to_owned(),
+            commit: commit_hash(),
+            dirty: is_work_tree_dirty(),
+            tools: tool_versions(),
+            ..Default::default()
+        };
+
+        for input in INPUT_PATHS {
+            let path = paths::PROJECT_ROOT.join(input);
+            if path.is_file() {
+                manifest.inputs.insert(input.to_string(), sha256_of(&path)?);
+            } else if path.is_dir() {
+                for file in list_files(&path)? {
+                    manifest.inputs.insert(
+                        format!("{}/{}", input, url_path(&file)),
+                        sha256_of(&path.join(&file))?,
+                    );
+                }
+            }
+        }
+
+        for file in list_files(web_dist_path)? {
+            let name = url_path(&file);
+            if name == BUILD_MANIFEST_FILE {
+                continue;
+            }
+            let content = fs::read(web_dist_path.join(&file))?;
+            manifest.outputs.insert(
+                name,
+                OutputFile {
+                    size: content.len() as u64,
+                    sha256: to_hex(&Sha256::digest(&content)),
+                },
+            );
+        }
+
+        Ok(manifest)
+    }
+
+    pub fn load(web_dist_path: &Path) -> Result<Self> {
+        serde_json::from_slice(&fs::read(
+            web_dist_path.join(BUILD_MANIFEST_FILE),
+        )?)
+        .map_err(Error::other)
+    }
+
+    pub fn save(&self, web_dist_path: &Path) -> TaskResult {
+        fs::write(
+            web_dist_path.join(BUILD_MANIFEST_FILE),
+            serde_json::to_string_pretty(self).map_err(Error::other)?,
+        )
+    }
+
+    /// Human readable differences with `other` (empty if the same)
+    pub fn diff(&self, other: &BuildManifest) -> Vec<String> {
+        let mut differences = vec![];
+
+        for (field, this, that) in [
+            ("profile", &self.profile, &other.profile),
+            ("commit", &self.commit, &other.commit),
+        ] {
+            if this != that {
+                differences.push(format!("{}: {} != {}", field, this, that));
+            }
+        }
+        diff_maps("tool", &self.tools, &other.tools, &mut differences);
+        diff_maps("input", &self.inputs, &other.inputs, &mut differences);
+        diff_maps("output", &self.outputs, &other.outputs, &mut differences);
+
+        differences
+    }
+}
+
+/// Write build manifest of distribution in `web_dist_path`
+pub fn write_build_manifest(web_dist_path: &Path, release: bool) -> TaskResult {
+    let manifest = BuildManifest::collect(web_dist_path, release)?;
+    log::info!(
+        "[xtask] Writing {} ({} outputs, {} inputs)",
+        web_dist_path.join(BUILD_MANIFEST_FILE).display(),
+        manifest.outputs.len(),
+        manifest.inputs.len()
+    );
+    manifest.save(web_dist_path)
+}
+
+fn diff_maps<T: PartialEq + std::fmt::Debug>(
+    kind: &str,
+    this: &BTreeMap<String, T>,
+    that: &BTreeMap<String, T>,
+    differences: &mut Vec<String>,
+) {
+    for (name, value) in this.iter() {
+        match that.get(name) {
+            Some(other) if other == value => {}
+            Some(other) => differences
+                .push(format!("{} {}: {:?} != {:?}", kind, name, value, other)),
+            None => {
+                differences.push(format!("{} {}: only in first", kind, name))
+            }
+        }
+    }
+    for name in that.keys().filter(|name| !this.contains_key(*name)) {
+        differences.push(format!("{} {}: only in second", kind, name));
+    }
+}
+
+fn list_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = vec![];
+    dir.for_each_file_recursively(FailurePolicy::FailFast, |relative_path| {
+        files.push(relative_path.to_owned());
+        OK
+    })?;
+    files.sort();
+    Ok(files)
+}
+
+fn sha256_of(path: &Path) -> Result<String> {
+    Ok(to_hex(&Sha256::digest(fs::read(path)?)))
+}
+
+/// First line of `--version` of each tool (`not installed` if not runnable)
+fn tool_versions() -> BTreeMap<String, String> {
+    let mut versions: BTreeMap<String, String> = TOOLS
+        .iter()
+        .map(|(name, command, args)| {
+            let version = Command::new(command)
+                .args(*args)
+                .output()
+                .ok()
+                .filter(|output| output.status.success())
+                .and_then(|output| {
+                    String::from_utf8_lossy(&output.stdout)
+                        .lines()
+                        .next()
+                        .map(str::to_owned)
+                })
+                .unwrap_or_else(|| "not installed".to_owned());
+            (name.to_string(), version)
+        })
+        .collect();
+
+    let lock_path = paths::PROJECT_ROOT.join("Cargo.lock");
+    for name in LIBRARY_TOOLS {
+        let version = locked_package_version(&lock_path, name)
+            .unwrap_or_else(|_| "unknown".to_owned());
+        versions.insert(name.to_string(), version);
+    }
+
+    versions
+}
+
+fn is_work_tree_dirty() -> bool {
+    Command::new("git")
+        .args(["status", "--porcelain"])
+        .current_dir(*paths::PROJECT_ROOT)
+        .output()
+        .is_ok_and(|output| !output.stdout.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_diffs_outputs_of_manifests() {
+        let output = |sha256: &str| OutputFile {
+            size: 1,
+            sha256: sha256.to_owned(),
+        };
+        let mut first = BuildManifest::default();
+        first.outputs.insert("index.html".to_owned(), output("aa"));
+        first.outputs.insert("a.js".to_owned(), output("bb"));
+        let mut second = BuildManifest::default();
+        second.outputs.insert("index.html".to_owned(), output("aa"));
+        second.outputs.insert("b.js".to_owned(), output("bb"));
+
+        assert!(first.diff(&first).is_empty());
+        assert_eq!(
+            first.diff(&second),
+            ["output a.js: only in first", "output b.js: only in second"]
+        );
+
+        second.outputs.insert("a.js".to_owned(), output("cc"));
+        second.outputs.remove("b.js");
+        assert_eq!(first.diff(&second).len(), 1);
+    }
+}
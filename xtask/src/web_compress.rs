@@ -0,0 +1,223 @@
+use std::fs;
+use std::io::{Error, ErrorKind, Result, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use brotli::enc::BrotliEncoderParams;
+use flate2::Compression;
+use flate2::write::GzEncoder;
+
+use crate::core_dist::{
+    DistributionPath, FailurePolicy, OK, TaskResult, task_option, task_swarm,
+};
+
+////////////////////////////////////////////////////////////////////////////////
+// Compression Settings
+////////////////////////////////////////////////////////////////////////////////
+
+const COMPRESS_EXTENSIONS: &[&[u8]] =
+    &[b"html", b"css", b"js", b"wasm", b"otf"];
+
+/// Minimal fraction of size compression should save to keep compressed file
+const DEFAULT_MIN_SAVING: f64 = 0.1;
+
+/// Overrides `DEFAULT_MIN_SAVING` (e.g. `--min-saving 0.25`)
+const MIN_SAVING_OPTION: &str = "--min-saving";
+
+const BROTLI_QUALITY: i32 = 11;
+const BROTLI_WINDOW: i32 = 22;
+
+////////////////////////////////////////////////////////////////////////////////
+// Pre-compression
+////////////////////////////////////////////////////////////////////////////////
+
+/// Encodings written next to original file (`<file>.<extension>`)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Encoding {
+    Gzip,
+    Brotli,
+}
+
+impl Encoding {
+    pub const ALL: [Encoding; 2] = [Encoding::Gzip, Encoding::Brotli];
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Encoding::Gzip => "gz",
+            Encoding::Brotli => "br",
+        }
+    }
+
+    /// Compress with best (slowest) settings
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xtask::web_compress::Encoding;
+    ///
+    /// let data = "shards ".repeat(64);
+    /// for encoding in Encoding::ALL {
+    ///     let compressed = encoding.compress(data.as_bytes()).unwrap();
+    ///     assert!(compressed.len() < data.len() / 4);
+    /// }
+    /// ```
+    pub fn compress(&self, data: &[u8]) -> Result<Vec<u8>> {
+        match self {
+            Encoding::Gzip => {
+                let mut encoder = GzEncoder::new(vec![], Compression::best());
+                encoder.write_all(data)?;
+                encoder.finish()
+            }
+            Encoding::Brotli => {
+                let params = BrotliEncoderParams {
+                    quality: BROTLI_QUALITY,
+                    lgwin: BROTLI_WINDOW,
+                    ..Default::default()
+                };
+                let mut compressed = vec![];
+                brotli::BrotliCompress(
+                    &mut &data[..],
+                    &mut compressed,
+                    &params,
+                )?;
+                Ok(compressed)
+            }
+        }
+    }
+}
+
+/// Sizes of one pre-compressed file (`None` if not worth compressing)
+struct CompressionStat {
+    relative_path: PathBuf,
+    original: usize,
+    compressed: [Option<usize>; 2],
+}
+
+/// Write `.gz` and `.br` siblings of every compressible file in
+/// `web_dist_path` (in parallel) and log summary table.
+/// Errors are handled by `FailurePolicy` of release build.
+pub fn precompress_web_distribution(web_dist_path: &Path) -> TaskResult {
+    let min_saving = min_saving()?;
+    let stats = Mutex::new(vec![]);
+
+    let compress_worker = |relative_path: &PathBuf| {
+        let stat = precompress_file(web_dist_path, relative_path, min_saving)
+            .map_err(|e| {
+            Error::new(
+                e.kind(),
+                format!(
+                    "failed to pre-compress {}: {}",
+                    relative_path.display(),
+                    e
+                ),
+            )
+        })?;
+        stats.lock().expect("poisoned stats").push(stat);
+        OK
+    };
+
+    let policy = FailurePolicy::for_build(true);
+    task_swarm(policy, compress_worker, |send_task| {
+        web_dist_path.for_each_file_recursively(policy, |relative_path| {
+            if relative_path.contains_any_extension(COMPRESS_EXTENSIONS) {
+                send_task(relative_path.to_owned(), relative_path.to_owned());
+            }
+            OK
+        })?;
+        OK
+    })?;
+
+    let mut stats = stats.into_inner().expect("poisoned stats");
+    stats.sort_by(|a, b| a.relative_path.cmp(&b.relative_path));
+    log_summary(&stats, min_saving);
+
+    OK
+}
+
+fn precompress_file(
+    web_dist_path: &Path,
+    relative_path: &Path,
+    min_saving: f64,
+) -> Result<CompressionStat> {
+    let path = web_dist_path.join(relative_path);
+    let data = fs::read(&path)?;
+    let max_size = (data.len() as f64 * (1.0 - min_saving)) as usize;
+    let mut compressed_sizes = [None; 2];
+
+    for (encoding, compressed_size) in
+        Encoding::ALL.iter().zip(compressed_sizes.iter_mut())
+    {
+        let mut compressed_path = path.clone().into_os_string();
+        compressed_path.push(".");
+        compressed_path.push(encoding.extension());
+
+        let compressed = encoding.compress(&data)?;
+        if compressed.len() <= max_size {
+            fs::write(&compressed_path, &compressed)?;
+            *compressed_size = Some(compressed.len());
+        } else if fs::exists(&compressed_path)? {
+            // stale from previous build
+            fs::remove_file(&compressed_path)?;
+        }
+    }
+
+    Ok(CompressionStat {
+        relative_path: relative_path.to_owned(),
+        original: data.len(),
+        compressed: compressed_sizes,
+    })
+}
+
+fn min_saving() -> Result<f64> {
+    let Some(value) = task_option(MIN_SAVING_OPTION) else {
+        return Ok(DEFAULT_MIN_SAVING);
+    };
+    match value.parse::<f64>() {
+        Ok(min_saving) if (0.0..1.0).contains(&min_saving) => Ok(min_saving),
+        _ => Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!(
+                "{} expects fraction in [0, 1), got {}",
+                MIN_SAVING_OPTION, value
+            ),
+        )),
+    }
+}
+
+fn log_summary(stats: &[CompressionStat], min_saving: f64) {
+    let name_width = stats
+        .iter()
+        .map(|stat| stat.relative_path.to_string_lossy().len())
+        .max()
+        .unwrap_or_default()
+        .max("file".len());
+    let size_cell = |size: Option<usize>, original: usize| match size {
+        Some(size) => format!(
+            "{:>10} {:>4.0}%",
+            size,
+            size as f64 * 100.0 / original.max(1) as f64
+        ),
+        None => format!("{:>16}", "-"),
+    };
+
+    log::info!(
+        "[xtask] Pre-compressed (kept if saving at least {:.0}%):",
+        min_saving * 100.0
+    );
+    log::info!(
+        "[xtask] {:name_width$} {:>10} {:>16} {:>16}",
+        "file",
+        "original",
+        "gzip",
+        "brotli"
+    );
+    for stat in stats {
+        log::info!(
+            "[xtask] {:name_width$} {:>10} {} {}",
+            stat.relative_path.to_string_lossy(),
+            stat.original,
+            size_cell(stat.compressed[0], stat.original),
+            size_cell(stat.compressed[1], stat.original),
+        );
+    }
+}
@@ -0,0 +1,422 @@
+use std::fs::{self, File};
+use std::io::{Error, ErrorKind, Result};
+use std::path::{Path, PathBuf};
+
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+
+use crate::core_dist::{
+    DistributionPath, FailurePolicy, OK, TaskResult, make_each_directory,
+    task_option, url_path,
+};
+use crate::paths;
+use crate::web_build_manifest::BuildManifest;
+use crate::web_template::shards_browser_version;
+
+////////////////////////////////////////////////////////////////////////////////
+// Deploy Settings
+////////////////////////////////////////////////////////////////////////////////
+
+/// Package file is `<prefix><version><extension>`
+const PACKAGE_PREFIX: &str = "shards-web-";
+const PACKAGE_EXTENSION: &str = ".tar.gz";
+
+/// Layout of deploy target directory: `releases/<version>/` and `current`
+/// symlink to one of them (point web server root at `current`)
+const RELEASES_SUBDIR: &str = "releases";
+const CURRENT_LINK: &str = "current";
+/// Versions in order of deployment (last is current)
+const HISTORY_FILE: &str = "releases.txt";
+
+/// Releases kept in target directory unless `--keep` is passed
+const DEFAULT_KEEP_RELEASES: usize = 5;
+
+////////////////////////////////////////////////////////////////////////////////
+// CLI Tasks
+////////////////////////////////////////////////////////////////////////////////
+
+/// Pack release distribution into `target/packages/` named by shards-browser
+/// version and commit it was built from
+pub fn package_web_distribution() -> TaskResult {
+    let web_dist_path = paths::BUILD_PATH.join(paths::WEB_DIST_SUBDIRECTORY);
+    let manifest = BuildManifest::load(&web_dist_path)?;
+    if manifest.profile != "release" {
+        return Err(Error::other(format!(
+            "{} is not release distribution",
+            web_dist_path.display()
+        )));
+    }
+
+    let mut version =
+        format!("{}-{}", shards_browser_version()?, manifest.commit);
+    if manifest.dirty {
+        version.push_str("-dirty");
+    }
+
+    let package_path = paths::BUILD_PATH
+        .join(paths::PACKAGES_SUBDIRECTORY)
+        .join(package_file_name(&version));
+    pack_distribution(&web_dist_path, &package_path)
+}
+
+/// Unpack package (`--package`, last built one by default) into
+/// `releases/<version>/` of `--target` and switch `current` to it, keeping
+/// `--keep` latest releases
+pub fn deploy() -> TaskResult {
+    let target = deploy_target()?;
+    let package_path = match task_option("--package") {
+        Some(package) => PathBuf::from(package),
+        None => latest_package()?,
+    };
+    let keep = match task_option("--keep") {
+        Some(keep) => keep.parse().map_err(|_| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!("--keep expects number of releases, got {}", keep),
+            )
+        })?,
+        None => DEFAULT_KEEP_RELEASES,
+    };
+
+    deploy_package(&package_path, &target, keep).map(drop)
+}
+
+/// Switch `current` of `--target` back to previously deployed release
+pub fn rollback() -> TaskResult {
+    rollback_release(&deploy_target()?).map(drop)
+}
+
+fn deploy_target() -> Result<PathBuf> {
+    task_option("--target").map(PathBuf::from).ok_or_else(|| {
+        Error::new(ErrorKind::InvalidInput, "--target <dir> is required")
+    })
+}
+
+/// Most recently written package of `target/packages/`
+fn latest_package() -> Result<PathBuf> {
+    let packages_path = paths::BUILD_PATH.join(paths::PACKAGES_SUBDIRECTORY);
+    let mut latest: Option<(std::time::SystemTime, PathBuf)> = None;
+    for entry in fs::read_dir(&packages_path)? {
+        let entry = entry?;
+        if package_version(&entry.path()).is_err() {
+            continue;
+        }
+        let modified = entry.metadata()?.modified()?;
+        if latest.as_ref().is_none_or(|(time, _)| modified > *time) {
+            latest = Some((modified, entry.path()));
+        }
+    }
+
+    latest.map(|(_, path)| path).ok_or_else(|| {
+        Error::new(
+            ErrorKind::NotFound,
+            format!(
+                "no packages in {} (run package-web-dist)",
+                packages_path.display()
+            ),
+        )
+    })
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// Packaging
+////////////////////////////////////////////////////////////////////////////////
+
+/// File name of package of `version`
+///
+/// # Examples
+///
+/// ```
+/// use xtask::web_deploy::package_file_name;
+///
+/// assert_eq!(
+///     package_file_name("0.1.0-9caa1a8"),
+///     "shards-web-0.1.0-9caa1a8.tar.gz"
+/// );
+/// ```
+pub fn package_file_name(version: &str) -> String {
+    format!("{}{}{}", PACKAGE_PREFIX, version, PACKAGE_EXTENSION)
+}
+
+/// Version of package by its file name (validated to be usable as directory
+/// name)
+///
+/// # Examples
+///
+/// ```
+/// use std::path::Path;
+/// use xtask::web_deploy::package_version;
+///
+/// let package = Path::new("target/packages/shards-web-0.1.0-9caa1a8.tar.gz");
+/// assert_eq!(package_version(package).unwrap(), "0.1.0-9caa1a8");
+/// assert!(package_version(Path::new("shards-web-.tar.gz")).is_err());
+/// assert!(package_version(Path::new("web-dist.zip")).is_err());
+/// ```
+pub fn package_version(package_path: &Path) -> Result<String> {
+    package_path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .and_then(|name| name.strip_prefix(PACKAGE_PREFIX))
+        .and_then(|name| name.strip_suffix(PACKAGE_EXTENSION))
+        .filter(|version| is_valid_version(version))
+        .map(str::to_owned)
+        .ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "{} is not named {}<version>{}",
+                    package_path.display(),
+                    PACKAGE_PREFIX,
+                    PACKAGE_EXTENSION
+                ),
+            )
+        })
+}
+
+fn is_valid_version(version: &str) -> bool {
+    !version.is_empty()
+        && !version.starts_with('.')
+        && version
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "._-+".contains(c))
+}
+
+/// Write gzipped tarball of `web_dist_path` to `package_path` (entries are
+/// sorted and have fixed mode and time, so same distribution gives same
+/// package)
+pub fn pack_distribution(
+    web_dist_path: &Path,
+    package_path: &Path,
+) -> TaskResult {
+    let mut files = vec![];
+    web_dist_path.for_each_file_recursively(
+        FailurePolicy::FailFast,
+        |relative_path| {
+            files.push(relative_path.to_owned());
+            OK
+        },
+    )?;
+    files.sort();
+
+    if let Some(parent) = package_path.parent() {
+        make_each_directory(parent)?;
+    }
+    log::info!(
+        "[xtask] Packing {} files of {} into {}",
+        files.len(),
+        web_dist_path.display(),
+        package_path.display()
+    );
+
+    let mut tar = tar::Builder::new(GzEncoder::new(
+        File::create(package_path)?,
+        Compression::best(),
+    ));
+    for file in files {
+        let content = fs::read(web_dist_path.join(&file))?;
+        let mut header = tar::Header::new_gnu();
+        header.set_size(content.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(0);
+        header.set_entry_type(tar::EntryType::Regular);
+        tar.append_data(&mut header, url_path(&file), content.as_slice())?;
+    }
+    tar.into_inner()?.finish()?;
+
+    OK
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// Releases
+////////////////////////////////////////////////////////////////////////////////
+
+/// Unpack package into `releases/<version>/` of `target`, atomically switch
+/// `current` to it and remove releases beyond `keep` latest ones (returns
+/// version)
+pub fn deploy_package(
+    package_path: &Path,
+    target: &Path,
+    keep: usize,
+) -> Result<String> {
+    let version = package_version(package_path)?;
+    let releases_path = target.join(RELEASES_SUBDIR);
+    let release_path = releases_path.join(&version);
+    let mut history = read_history(target)?;
+
+    if history.last() == Some(&version) && release_path.is_dir() {
+        log::info!("[xtask] {} is already current release", version);
+        return Ok(version);
+    }
+
+    // unpack next to release directory, then move it in place
+    let partial_path = releases_path.join(format!(".{}.partial", version));
+    remove_directory(&partial_path)?;
+    remove_directory(&release_path)?;
+    make_each_directory(&partial_path)?;
+    log::info!(
+        "[xtask] Unpacking {} into {}",
+        package_path.display(),
+        release_path.display()
+    );
+    tar::Archive::new(GzDecoder::new(File::open(package_path)?))
+        .unpack(&partial_path)?;
+    fs::rename(&partial_path, &release_path)?;
+
+    switch_current(target, &version)?;
+    history.retain(|deployed| *deployed != version);
+    history.push(version.clone());
+
+    // keep current one at least
+    let removed = history.len().saturating_sub(keep.max(1));
+    for old_version in history.drain(..removed) {
+        remove_directory(&releases_path.join(old_version))?;
+    }
+    write_history(target, &history)?;
+
+    Ok(version)
+}
+
+/// Switch `current` of `target` to release deployed before it and remove
+/// current one (returns version switched to)
+pub fn rollback_release(target: &Path) -> Result<String> {
+    let mut history = read_history(target)?;
+    if history.len() < 2 {
+        return Err(Error::new(
+            ErrorKind::NotFound,
+            format!("{} has no previous release", target.display()),
+        ));
+    }
+
+    let rolled_back = history.pop().unwrap_or_default();
+    let version = history.last().cloned().unwrap_or_default();
+    log::info!("[xtask] Rolling back {} to {}", rolled_back, version);
+
+    switch_current(target, &version)?;
+    write_history(target, &history)?;
+    remove_directory(&target.join(RELEASES_SUBDIR).join(rolled_back))?;
+
+    Ok(version)
+}
+
+/// Version `current` of `target` points to
+pub fn current_release(target: &Path) -> Result<String> {
+    let link = fs::read_link(target.join(CURRENT_LINK))?;
+    link.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!("{} points to {}", CURRENT_LINK, link.display()),
+            )
+        })
+}
+
+/// Point `current` at release by renaming new link over it (web server never
+/// sees missing or half-written root)
+fn switch_current(target: &Path, version: &str) -> TaskResult {
+    let next_link = target.join(format!(".{}.next", CURRENT_LINK));
+    if next_link.symlink_metadata().is_ok() {
+        fs::remove_file(&next_link)?;
+    }
+
+    log::info!(
+        "[xtask] Switching {} to {}",
+        target.join(CURRENT_LINK).display(),
+        version
+    );
+    // relative, so target directory can be moved or mounted elsewhere
+    symlink_dir(&Path::new(RELEASES_SUBDIR).join(version), &next_link)?;
+    fs::rename(&next_link, target.join(CURRENT_LINK))
+}
+
+#[cfg(unix)]
+fn symlink_dir(original: &Path, link: &Path) -> TaskResult {
+    std::os::unix::fs::symlink(original, link)
+}
+
+#[cfg(not(unix))]
+fn symlink_dir(_original: &Path, _link: &Path) -> TaskResult {
+    Err(Error::new(
+        ErrorKind::Unsupported,
+        "deploy switches releases by symlink (unix only)",
+    ))
+}
+
+fn read_history(target: &Path) -> Result<Vec<String>> {
+    match fs::read_to_string(target.join(HISTORY_FILE)) {
+        Ok(text) => Ok(text
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(str::to_owned)
+            .collect()),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(vec![]),
+        Err(e) => Err(e),
+    }
+}
+
+/// Written to temporary file first, so history is never half-written
+fn write_history(target: &Path, history: &[String]) -> TaskResult {
+    let next_path = target.join(format!(".{}.next", HISTORY_FILE));
+    fs::write(&next_path, history.join("\n") + "\n")?;
+    fs::rename(&next_path, target.join(HISTORY_FILE))
+}
+
+fn remove_directory(path: &Path) -> TaskResult {
+    if !path.exists() {
+        return OK;
+    }
+    log::info!("[xtask] Removing {}", path.display());
+    fs::remove_dir_all(path)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    fn package(dir: &Path, version: &str) -> PathBuf {
+        let web_dist = dir.join(format!("web-dist-{}", version));
+        fs::create_dir_all(web_dist.join("fonts")).unwrap();
+        fs::write(web_dist.join("index.html"), version).unwrap();
+        fs::write(web_dist.join("fonts/a.otf"), "font").unwrap();
+
+        let package_path = dir.join(package_file_name(version));
+        pack_distribution(&web_dist, &package_path).unwrap();
+        package_path
+    }
+
+    #[test]
+    fn it_deploys_prunes_and_rolls_back_releases() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("www");
+        let packages: Vec<_> = ["1", "2", "3"]
+            .iter()
+            .map(|version| package(dir.path(), version))
+            .collect();
+        let current_index =
+            || fs::read_to_string(target.join("current/index.html")).unwrap();
+
+        for package_path in packages.iter() {
+            deploy_package(package_path, &target, 2).unwrap();
+        }
+        assert_eq!(current_release(&target).unwrap(), "3");
+        assert_eq!(current_index(), "3");
+        assert!(target.join("current/fonts/a.otf").is_file());
+        assert!(!target.join("releases/1").exists());
+        assert_eq!(read_history(&target).unwrap(), ["2", "3"]);
+
+        assert_eq!(rollback_release(&target).unwrap(), "2");
+        assert_eq!(current_index(), "2");
+        assert!(!target.join("releases/3").exists());
+        assert!(rollback_release(&target).is_err());
+
+        // same package packs into same bytes
+        let repacked = dir.path().join("repacked").join(package_file_name("1"));
+        pack_distribution(&dir.path().join("web-dist-1"), &repacked).unwrap();
+        assert_eq!(
+            fs::read(&packages[0]).unwrap(),
+            fs::read(repacked).unwrap()
+        );
+    }
+}
@@ -1,41 +1,48 @@
+use std::fs;
+use std::io::Error;
 use std::path::{Path, PathBuf};
 use std::sync::LazyLock;
-use std::sync::mpsc;
-use std::thread;
-use std::time::Duration;
 
 use crate::core_dist::{
-    DistributionPath, OK, TaskResult, cargo, make_each_directory,
-    shell_log_piped, transparent_shell,
+    DistributionPath, FailurePolicy, OK, TaskResult, make_each_directory,
+    task_swarm,
 };
 use crate::paths;
-
-////////////////////////////////////////////////////////////////////////////////
-// Shell Commands
-////////////////////////////////////////////////////////////////////////////////
-
-const HTTP_SERVER: &str = "simple-http-server";
-const WASM_PACK: &str = "wasm-pack";
-const MINHTML: &str = "minhtml";
+use crate::web_app::{WebApp, generate_web_app_files};
+use crate::web_build_manifest::write_build_manifest;
+use crate::web_compress::precompress_web_distribution;
+use crate::web_error_pages::{ERROR_PAGE_TEMPLATE_FILE, ErrorPages};
+use crate::web_fingerprint::fingerprint_web_distribution;
+use crate::web_font_subset::subset_web_fonts;
+use crate::web_headers::generated_header_rules_path;
+use crate::web_integrity::secure_web_distribution;
+use crate::web_service_worker::generate_service_worker;
+use crate::web_size::check_size_budgets;
+use crate::web_template::{PARTIALS_SUBDIR, Template};
+use crate::web_wasm_bindgen::{
+    WASM_VARIANTS, build_wasm_bindgen_package, clean_wasm_package,
+    selected_wasm_variants,
+};
+use crate::web_wasm_loader::{WASM_LOADER_FILE, wasm_loader_source};
+use crate::web_wasm_opt::{
+    DEBUG_SIDECAR_SUFFIX, UNOPTIMIZED_SUFFIX, WasmProfile, optimize_wasm_module,
+};
 
 ////////////////////////////////////////////////////////////////////////////////
 // Web Distribution Paths
 ////////////////////////////////////////////////////////////////////////////////
 
 const FRONT_PAGE_DIR: &str = "front-page";
-const ERROR_PAGE_SUBDIR: &str = "error_pages";
-const HIDDEN_ERROR_PAGE_DIR: &str = ".error_pages";
+pub(crate) const ERROR_PAGE_SUBDIR: &str = "error_pages";
+pub(crate) const HIDDEN_ERROR_PAGE_DIR: &str = ".error_pages";
 
 const MINIFY_EXTENSIONS: &[&[u8]] = &[b"html", b"css", b"js"];
 
-static MINIFY_DIR_RENAMING: LazyLock<[(PathBuf, PathBuf); 1]> =
-    LazyLock::new(|| {
-        [(
-            paths::PROJECT_ROOT
-                .join(FRONT_PAGE_DIR)
-                .join(ERROR_PAGE_SUBDIR),
-            PathBuf::from(HIDDEN_ERROR_PAGE_DIR),
-        )]
+static MINIFY_CFG: LazyLock<minify_html::Cfg> =
+    LazyLock::new(|| minify_html::Cfg {
+        minify_css: true,
+        minify_js: true,
+        ..Default::default()
     });
 
 ////////////////////////////////////////////////////////////////////////////////
@@ -48,18 +55,6 @@ pub fn build_web_distribution() -> TaskResult {
     build_web_distribution_by_path(&web_dist_path, &wasm_pkg_path, true)
 }
 
-pub fn serve_web_distribution() -> TaskResult {
-    prepare_serve_web_distribution()?;
-    let web_dist_path = paths::BUILD_PATH.join(paths::WEB_DIST_SUBDIRECTORY);
-    serve_web_distribution_by_path(&web_dist_path)
-}
-
-pub fn prepare_serve_web_distribution() -> TaskResult {
-    build_web_distribution()?;
-    cargo(&["install", HTTP_SERVER])?;
-    OK
-}
-
 pub fn build_web_distribution_dev() -> TaskResult {
     let web_dist_path =
         paths::BUILD_PATH.join(paths::WEB_DIST_DEV_SUBDIRECTORY);
@@ -68,259 +63,441 @@ pub fn build_web_distribution_dev() -> TaskResult {
     build_web_distribution_by_path(&web_dist_path, &wasm_pkg_path, false)
 }
 
-pub fn serve_web_distribution_dev() -> TaskResult {
-    prepare_serve_web_distribution_dev()?;
-    let web_dist_path =
-        paths::BUILD_PATH.join(paths::WEB_DIST_DEV_SUBDIRECTORY);
-    serve_web_distribution_by_path(&web_dist_path)
-}
-
-pub fn prepare_serve_web_distribution_dev() -> TaskResult {
-    build_web_distribution_dev()?;
-    cargo(&["install", HTTP_SERVER])?;
-    OK
-}
-
 ////////////////////////////////////////////////////////////////////////////////
-// Private
+// Build Stages
 ////////////////////////////////////////////////////////////////////////////////
 
-fn build_web_distribution_by_path(
+pub(crate) fn build_web_distribution_by_path(
     web_dist_path: &Path,
     wasm_pkg_path: &Path,
     release: bool,
 ) -> TaskResult {
-    cargo(&["install", WASM_PACK])?;
+    build_wasm_package(wasm_pkg_path, release)?;
 
-    make_each_directory(web_dist_path)?;
+    assemble_web_distribution(web_dist_path, wasm_pkg_path, release)?;
+
+    if release {
+        check_size_budgets(web_dist_path)?;
+    }
+    write_build_manifest(web_dist_path, release)?;
+
+    log::info!("[xtask] Done! Check: {}", web_dist_path.display());
+    OK
+}
 
-    let wasm_pkg_path_arg = wasm_pkg_path.to_string_lossy();
-    let wasm_pack_args: &[&str] = if release {
-        &[
-            "--verbose",
-            "build",
-            "shards-browser",
-            "--target",
-            "web",
-            "--out-dir",
-            &wasm_pkg_path_arg,
-        ]
+/// Build shards-browser (cargo + wasm-bindgen) into `wasm_pkg_path` and
+/// optimize it. Release package has every variant of `WASM_VARIANTS`,
+/// developer one only baseline (plus opt-in ones requested by `--threads`).
+pub(crate) fn build_wasm_package(
+    wasm_pkg_path: &Path,
+    release: bool,
+) -> TaskResult {
+    let profile = if release {
+        WasmProfile::Release
     } else {
-        &[
-            "--verbose",
-            "build",
-            "shards-browser",
-            "--dev",
-            "--target",
-            "web",
-            "--out-dir",
-            &wasm_pkg_path_arg,
-        ]
+        WasmProfile::Dev
     };
 
-    shell_log_piped(
-        "wasm-pack",
-        wasm_pack_args,
-        &[("RUSTFLAGS", "-Ctarget-cpu=mvp")],
-    )?;
+    clean_wasm_package(wasm_pkg_path)?;
+    for variant in selected_wasm_variants(release) {
+        build_wasm_bindgen_package(wasm_pkg_path, profile, variant)?;
+        optimize_wasm_module(
+            &wasm_pkg_path.join(variant.module_file()),
+            profile,
+            variant.wasm_opt_features,
+        )?;
+    }
+
+    OK
+}
 
-    let front_page_path = paths::PROJECT_ROOT.join(FRONT_PAGE_DIR);
+/// Build optimized baseline shards-browser keeping function names (for size
+/// attribution) into `wasm_pkg_path`.
+pub(crate) fn build_wasm_names_package(wasm_pkg_path: &Path) -> TaskResult {
+    let baseline = &WASM_VARIANTS[0];
+
+    clean_wasm_package(wasm_pkg_path)?;
+    build_wasm_bindgen_package(wasm_pkg_path, WasmProfile::Names, baseline)?;
+    optimize_wasm_module(
+        &wasm_pkg_path.join(baseline.module_file()),
+        WasmProfile::Names,
+        baseline.wasm_opt_features,
+    )
+}
 
-    // copy all wasm modules and js-bindings
+/// Copy wasm modules and js-bindings from `wasm_pkg_path` into
+/// `web_dist_path`.
+pub(crate) fn copy_wasm_package(
+    web_dist_path: &Path,
+    wasm_pkg_path: &Path,
+    release: bool,
+) -> TaskResult {
     log::debug!(
         "[xtask] Copying js and wasm from {} to {}",
         wasm_pkg_path.display(),
         web_dist_path.display()
     );
-    wasm_pkg_path.copy_file_tree_filtered(web_dist_path, |path| {
+    let policy = FailurePolicy::for_build(release);
+    wasm_pkg_path.copy_file_tree_filtered(web_dist_path, policy, |path| {
+        let name = path.to_string_lossy();
         path.contains_any_extension(&[b"js", b"wasm"])
+            && !name.ends_with(UNOPTIMIZED_SUFFIX)
+            && !name.ends_with(DEBUG_SIDECAR_SUFFIX)
     })?;
+    OK
+}
 
-    if release {
-        cargo(&["install", MINHTML])?;
+/// Put built wasm package and front-page together into `web_dist_path`.
+/// Release distribution is assembled from scratch (asset names depend on
+/// content) and post-processed as a whole.
+pub(crate) fn assemble_web_distribution(
+    web_dist_path: &Path,
+    wasm_pkg_path: &Path,
+    release: bool,
+) -> TaskResult {
+    if release && web_dist_path.exists() {
+        log::info!("[xtask] Cleaning {}", web_dist_path.display());
+        fs::remove_dir_all(web_dist_path)?;
+    }
+    let generated_rules_path = generated_header_rules_path(web_dist_path);
+    if release && generated_rules_path.exists() {
+        fs::remove_file(generated_rules_path)?;
+    }
 
-        // minify front-page html, css and js files
-        thread::scope(|s| minify_swarm(s, &front_page_path, web_dist_path))?;
+    make_each_directory(web_dist_path)?;
 
-        // copy rest
-        log::debug!(
-            "[xtask] Copying resources from {} to {}",
-            front_page_path.display(),
-            web_dist_path.display()
-        );
-        front_page_path.copy_file_tree_filtered(web_dist_path, |path| {
-            !path.contains_any_extension(&MINIFY_EXTENSIONS)
-        })?;
-    } else {
-        // copy whole front-page
-        log::debug!(
-            "[xtask] Copying everything from {} to {}",
-            front_page_path.display(),
-            web_dist_path.display()
-        );
-        front_page_path.copy_file_tree(web_dist_path)?;
+    copy_wasm_package(web_dist_path, wasm_pkg_path, release)?;
+
+    build_front_page(web_dist_path, release)?;
+
+    if release {
+        subset_web_fonts(web_dist_path)?;
+        fingerprint_web_distribution(web_dist_path)?;
+        secure_web_distribution(web_dist_path)?;
+        // cache version covers final pages
+        generate_service_worker(web_dist_path)?;
+        precompress_web_distribution(web_dist_path)?;
     }
 
-    log::info!("[xtask] Done! Check: {}", web_dist_path.display());
     OK
 }
 
-fn serve_web_distribution_by_path(web_dist_path: &Path) -> TaskResult {
-    let web_dist_path_str = web_dist_path.to_string_lossy();
-
-    transparent_shell(
-        HTTP_SERVER,
-        &[
-            "--nocache",
-            "-i",
-            "-p",
-            "8080",
-            "--ip",
-            "127.0.0.1",
-            &web_dist_path_str,
-        ],
-        &[],
-    )?;
+/// Copy (and minify in release) front-page into `web_dist_path`, templates
+/// (html, css and js files) are expanded first, error pages are rendered
+/// from template.
+pub(crate) fn build_front_page(
+    web_dist_path: &Path,
+    release: bool,
+) -> TaskResult {
+    let front_page_path = front_page_path();
+    let template = Template::for_front_page(&front_page_path, release)?;
+    let policy = FailurePolicy::for_build(release);
 
-    OK
+    build_error_pages(web_dist_path, &template, release)?;
+
+    if release {
+        // expand and minify front-page html, css and js files
+        let expanded_path =
+            paths::BUILD_PATH.join(paths::FRONT_PAGE_EXPANDED_SUBDIRECTORY);
+        if expanded_path.exists() {
+            fs::remove_dir_all(&expanded_path)?;
+        }
+        expand_front_page(&template, &front_page_path, &expanded_path, policy)?;
+        minify_swarm(&expanded_path, web_dist_path, policy)?;
+    } else {
+        expand_front_page(&template, &front_page_path, web_dist_path, policy)?;
+    }
+
+    // copy rest
+    log::debug!(
+        "[xtask] Copying resources from {} to {}",
+        front_page_path.display(),
+        web_dist_path.display()
+    );
+    front_page_path.copy_file_tree_filtered(web_dist_path, policy, |path| {
+        !path.contains_any_extension(MINIFY_EXTENSIONS)
+            && !is_template_source(path)
+    })?;
+
+    build_wasm_loader(web_dist_path, release)?;
+    generate_web_app_files(web_dist_path, &front_page_path)
 }
 
-fn minify_swarm<'a>(
-    s: &'a thread::Scope<'a, '_>,
-    input: &'a Path,
-    output: &'a Path,
+/// Update single front-page file (given relative to front-page) the same way
+/// `build_front_page` would do. Removes output if source file is gone.
+pub(crate) fn build_front_page_file(
+    web_dist_path: &Path,
+    relative_path: &Path,
+    release: bool,
 ) -> TaskResult {
-    let available_parallelism = thread::available_parallelism()?.get();
-    assert!(available_parallelism > 0, "0 parallelism?!");
-
-    let log_minify_error = |err| {
-        log::error!(
-            "[xtask] failed to call minify {} > {}: {}",
-            input.display(),
-            output.display(),
-            err
-        );
-    };
+    if relative_path.starts_with(PARTIALS_SUBDIR) {
+        // any file may include it
+        return build_front_page(web_dist_path, release);
+    }
+
+    let front_page_path = front_page_path();
+    let template = Template::for_front_page(&front_page_path, release)?;
+
+    if relative_path.starts_with(ERROR_PAGE_SUBDIR) {
+        return build_error_pages(web_dist_path, &template, release);
+    }
+
+    let full_input = front_page_path.join(relative_path);
+    let full_output = web_dist_path.join(relative_path);
 
-    // Specify sender type to help infer rest
-    type MinifyTask = (PathBuf, PathBuf); // minify(&input, &output)
-    let make_channel = || mpsc::sync_channel::<MinifyTask>(0);
-
-    // Spawn thread pool with pipe
-    let pipes: Vec<_> = (0..available_parallelism)
-        .map(|_| {
-            let (sender, receiver) = make_channel();
-
-            let minify_thread_func = move || {
-                receiver
-                    .iter()
-                    .map(|(input, output)| minify(&input, &output))
-                    .filter_map(|minify_result| minify_result.err())
-                    .for_each(log_minify_error)
-            };
-
-            s.spawn(minify_thread_func);
-
-            sender
-        })
-        .collect();
-
-    // for each file send minify task to any available pipe
-    input.for_each_file_recursively(|relative_path| {
-        if !relative_path.contains_any_extension(MINIFY_EXTENSIONS) {
-            return;
+    if !full_input.exists() {
+        if full_output.is_file() {
+            log::info!("[xtask] Removing {}", full_output.display());
+            fs::remove_file(&full_output)?;
         }
+        return OK;
+    }
 
-        let mut minify_args =
-            (input.join(relative_path), output.join(relative_path));
-
-        loop {
-            let mut all_disconnected = true;
-
-            for pipe in pipes.iter() {
-                minify_args = if let Err(e) = pipe.try_send(minify_args) {
-                    match e {
-                        mpsc::TrySendError::Full(args) => {
-                            all_disconnected = false;
-                            args
-                        },
-                        mpsc::TrySendError::Disconnected(args) => args,
-                    }
-                } else {
-                    // successfully sent minify task in thread
-                    return;
-                }
-            }
+    if !relative_path.contains_any_extension(MINIFY_EXTENSIONS) {
+        front_page_path.copy_file_tree_filtered(
+            web_dist_path,
+            FailurePolicy::FailFast,
+            |path| path == relative_path,
+        )?;
+        // rendered icons replace copied source (`favicon.ico`)
+        if WebApp::load()?.is_icon_source(relative_path) {
+            generate_web_app_files(web_dist_path, &front_page_path)?;
+        }
+        OK
+    } else if release {
+        let expanded_path =
+            paths::BUILD_PATH.join(paths::FRONT_PAGE_EXPANDED_SUBDIRECTORY);
+        expand_file(
+            &template,
+            &front_page_path,
+            relative_path,
+            &expanded_path,
+        )?;
+        minify(&expanded_path.join(relative_path), &full_output)
+    } else {
+        expand_file(&template, &front_page_path, relative_path, web_dist_path)
+    }
+}
+
+/// Write loader of wasm modules already copied into `web_dist_path` (minified
+/// in release)
+pub(crate) fn build_wasm_loader(
+    web_dist_path: &Path,
+    release: bool,
+) -> TaskResult {
+    let source = wasm_loader_source(web_dist_path, &front_page_path())?;
+    let full_output = web_dist_path.join(WASM_LOADER_FILE);
+    log::info!("[xtask] Writing {}", full_output.display());
+
+    if release {
+        let full_input = paths::BUILD_PATH
+            .join(paths::FRONT_PAGE_EXPANDED_SUBDIRECTORY)
+            .join(WASM_LOADER_FILE);
+        make_each_directory(full_input.parent().expect("has parent"))?;
+        fs::write(&full_input, source)?;
+        minify(&full_input, &full_output)
+    } else {
+        fs::write(full_output, source)
+    }
+}
+
+pub(crate) fn front_page_path() -> PathBuf {
+    paths::PROJECT_ROOT.join(FRONT_PAGE_DIR)
+}
+
+/// Render error pages from template into `.error_pages` (release, minified)
+/// or `error_pages` (developer) of `web_dist_path`.
+pub(crate) fn build_error_pages(
+    web_dist_path: &Path,
+    template: &Template,
+    release: bool,
+) -> TaskResult {
+    let mut error_pages =
+        ErrorPages::load(&front_page_path().join(ERROR_PAGE_SUBDIR))?;
+    error_pages.template = template.expand(
+        &error_pages.template,
+        &format!("{}/{}", ERROR_PAGE_SUBDIR, ERROR_PAGE_TEMPLATE_FILE),
+    )?;
+    let output_path = web_dist_path.join(if release {
+        HIDDEN_ERROR_PAGE_DIR
+    } else {
+        ERROR_PAGE_SUBDIR
+    });
+    log::debug!(
+        "[xtask] Rendering error pages {:?} into {}",
+        error_pages.codes(),
+        output_path.display()
+    );
+
+    // removed pages should not stay
+    if output_path.exists() {
+        fs::remove_dir_all(&output_path)?;
+    }
+    if !release {
+        return error_pages.write(&output_path);
+    }
+
+    let staging_path = paths::BUILD_PATH.join(paths::ERROR_PAGES_SUBDIRECTORY);
+    if staging_path.exists() {
+        fs::remove_dir_all(&staging_path)?;
+    }
+    error_pages.write(&staging_path)?;
+    for code in error_pages.codes() {
+        let file_name = format!("{}.html", code);
+        minify(
+            &staging_path.join(&file_name),
+            &output_path.join(&file_name),
+        )?;
+    }
+
+    OK
+}
 
-            if all_disconnected {
-                log::error!(
-                    "[xtask] failed to send minify task [{} > {}]: all thread pipes disconnected",
-                    minify_args.0.display(),
-                    minify_args.1.display(),
+////////////////////////////////////////////////////////////////////////////////
+// Private
+////////////////////////////////////////////////////////////////////////////////
+
+fn minify_swarm(
+    input: &Path,
+    output: &Path,
+    policy: FailurePolicy,
+) -> TaskResult {
+    let minify_worker =
+        |(input, output): &(PathBuf, PathBuf)| minify(input, output);
+
+    // for each file send minify task to swarm (failed files are listed by
+    // error)
+    task_swarm(policy, minify_worker, |send_task| {
+        input.for_each_file_recursively(policy, |relative_path| {
+            if relative_path.contains_any_extension(MINIFY_EXTENSIONS) {
+                send_task(
+                    relative_path.to_owned(),
+                    (input.join(relative_path), output.join(relative_path)),
                 );
-                return;
             }
-
-            thread::sleep(Duration::from_millis(1));
-        }
-    })
+            OK
+        })?;
+        OK
+    })?;
+    OK
 }
 
+/// Minify html (css and js files as contents of `<style>` and `<script>`)
 fn minify(full_input: &Path, full_output: &Path) -> TaskResult {
     assert!(full_input.is_absolute());
     assert!(full_output.is_absolute());
 
-    // Inject error directory renaming
-    let full_output =
-        handle_minify_directory_renaming(&full_input, &full_output);
+    let error = |e: Error| {
+        Error::new(
+            e.kind(),
+            format!(
+                "failed to minify {} > {}: {}",
+                full_input.display(),
+                full_output.display(),
+                e
+            ),
+        )
+    };
+
+    let source = fs::read(full_input).map_err(error)?;
+    let wrapper = if full_input.contains_any_extension(&[b"css"]) {
+        Some(("<style>", "</style>"))
+    } else if full_input.contains_any_extension(&[b"js"]) {
+        Some(("<script>", "</script>"))
+    } else {
+        None
+    };
+
+    let minified = match wrapper {
+        Some((open, close)) => {
+            let wrapped = [open.as_bytes(), &source, close.as_bytes()].concat();
+            let minified = minify_html::minify(&wrapped, &MINIFY_CFG);
+            minified
+                .strip_prefix(open.as_bytes())
+                .and_then(|minified| minified.strip_suffix(close.as_bytes()))
+                .map(<[u8]>::to_vec)
+                .ok_or_else(|| {
+                    error(Error::other(format!("unexpected {} output", open)))
+                })?
+        }
+        None => minify_html::minify(&source, &MINIFY_CFG),
+    };
 
     if let Some(dest_path_parent) = full_output.parent() {
-        make_each_directory(dest_path_parent)?;
+        make_each_directory(dest_path_parent).map_err(error)?;
     }
+    fs::write(full_output, minified).map_err(error)
+}
 
-    let full_output = full_output.to_str().unwrap_or_default();
-    let full_input = full_input.to_str().unwrap_or_default();
-
-    shell_log_piped(
-        "minhtml",
-        &[
-            "--minify-js",
-            "--minify-css",
-            "-o",
-            full_output,
-            full_input,
-        ],
-        &[],
-    )
+/// Expand every html, css and js file of front-page (but partials and error
+/// pages) into `output_path`
+fn expand_front_page(
+    template: &Template,
+    front_page_path: &Path,
+    output_path: &Path,
+    policy: FailurePolicy,
+) -> TaskResult {
+    front_page_path.for_each_file_recursively(policy, |relative_path| {
+        if relative_path.contains_any_extension(MINIFY_EXTENSIONS)
+            && !is_template_source(relative_path)
+        {
+            expand_file(template, front_page_path, relative_path, output_path)
+        } else {
+            OK
+        }
+    })?;
+    OK
 }
 
-fn handle_minify_directory_renaming(
-    full_input: &Path,
-    full_output: &Path,
-) -> PathBuf {
-    assert!(full_input.is_absolute());
-    assert!(full_output.is_absolute());
+fn expand_file(
+    template: &Template,
+    front_page_path: &Path,
+    relative_path: &Path,
+    output_path: &Path,
+) -> TaskResult {
+    let text = fs::read_to_string(front_page_path.join(relative_path))?;
+    let expanded = template.expand(&text, &relative_path.to_string_lossy())?;
 
-    // Find by trying stripping prefix of input dir and taking all successful.
-    // First candidate would be enough.
-    let mut candidates_it =
-        MINIFY_DIR_RENAMING
-            .iter()
-            .filter_map(|(path, renamed_path)| {
-                // try strip path and take renamed_path with
-                full_input
-                    .strip_prefix(path)
-                    .ok()
-                    .map(|relative_path| (relative_path, renamed_path))
-            });
-
-    if let Some((relative_path, renamed_path)) = candidates_it.next()
-        && let renamed_relative_output = renamed_path.join(relative_path)
-        && let cut_depth = renamed_relative_output.relative_depth()
-        && let Some(output_base) = full_output.cut_children(cut_depth)
-    {
-        output_base.join(renamed_relative_output)
-    } else {
-        full_output.to_owned()
+    let full_output = output_path.join(relative_path);
+    if let Some(parent) = full_output.parent() {
+        make_each_directory(parent)?;
+    }
+    fs::write(full_output, expanded)
+}
+
+/// Partials and error pages sources (relative to front-page) are only used
+/// through templates
+fn is_template_source(relative_path: &Path) -> bool {
+    relative_path.starts_with(PARTIALS_SUBDIR)
+        || relative_path.starts_with(ERROR_PAGE_SUBDIR)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_minifies_html_css_and_js_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let sources = [
+            ("a.html", "<p>  Hello  </p>\n", "<p>Hello"),
+            ("a.css", "a { color : red ; }\n", "a{color:red}"),
+            ("a.js", "let  x = 1 ;\nconsole.log( x )\n", "let x=1;"),
+        ];
+
+        for (name, source, minified) in sources {
+            let input = dir.path().join(name);
+            let output = dir.path().join("out").join(name);
+            fs::write(&input, source).unwrap();
+
+            minify(&input, &output).unwrap();
+
+            let output = fs::read_to_string(output).unwrap();
+            assert!(output.starts_with(minified), "{}: {}", name, output);
+        }
+
+        assert!(
+            minify(&dir.path().join("b.html"), &dir.path().join("c")).is_err()
+        );
     }
 }
@@ -0,0 +1,156 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{Error, ErrorKind, Result};
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::core_dist::{OK, TaskResult, make_each_directory};
+
+////////////////////////////////////////////////////////////////////////////////
+// Error Pages Sources
+////////////////////////////////////////////////////////////////////////////////
+
+/// Both are in front-page error pages directory (never copied as is)
+pub(crate) const ERROR_PAGE_TEMPLATE_FILE: &str = "template.html";
+pub(crate) const ERROR_PAGE_DATA_FILE: &str = "pages.toml";
+
+////////////////////////////////////////////////////////////////////////////////
+// Rendering
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Deserialize)]
+pub struct ErrorPage {
+    pub title: String,
+    pub message: Vec<String>,
+    #[serde(default)]
+    pub home_link: bool,
+}
+
+/// Error pages template and data by HTTP status code
+#[derive(Debug)]
+pub struct ErrorPages {
+    pub template: String,
+    pub pages: BTreeMap<u16, ErrorPage>,
+}
+
+#[derive(Deserialize)]
+struct ErrorPagesData {
+    pages: BTreeMap<u16, ErrorPage>,
+}
+
+impl ErrorPages {
+    /// Load `template.html` and `pages.toml` from `dir`
+    pub fn load(dir: &Path) -> Result<Self> {
+        let data_path = dir.join(ERROR_PAGE_DATA_FILE);
+        let data: ErrorPagesData =
+            toml::from_str(&fs::read_to_string(&data_path)?).map_err(|e| {
+                Error::new(
+                    ErrorKind::InvalidData,
+                    format!("{}: {}", data_path.display(), e),
+                )
+            })?;
+
+        Ok(ErrorPages {
+            template: fs::read_to_string(dir.join(ERROR_PAGE_TEMPLATE_FILE))?,
+            pages: data.pages,
+        })
+    }
+
+    /// HTTP status codes of pages (sorted)
+    pub fn codes(&self) -> Vec<u16> {
+        self.pages.keys().copied().collect()
+    }
+
+    /// Write every page as `<code>.html` into `output_dir`
+    pub fn write(&self, output_dir: &Path) -> TaskResult {
+        make_each_directory(output_dir)?;
+        for (code, page) in self.pages.iter() {
+            fs::write(
+                output_dir.join(format!("{}.html", code)),
+                render_error_page(&self.template, *code, page),
+            )?;
+        }
+
+        OK
+    }
+}
+
+/// Substitute `{{code}}`, `{{title}}` and `{{message}}` (lines of message and
+/// link to main page) of template
+///
+/// # Examples
+///
+/// ```
+/// use xtask::web_error_pages::{ErrorPage, render_error_page};
+///
+/// let page = ErrorPage {
+///     title: "Too Many Requests".to_owned(),
+///     message: vec!["Slow down & retry".to_owned()],
+///     home_link: false,
+/// };
+///
+/// assert_eq!(
+///     render_error_page("<title>{{code}} {{title}}</title>", 429, &page),
+///     "<title>429 Too Many Requests</title>"
+/// );
+/// assert_eq!(
+///     render_error_page("{{message}}", 429, &page),
+///     "            <div><span>Slow down &amp; retry</span></div>"
+/// );
+/// ```
+pub fn render_error_page(
+    template: &str,
+    code: u16,
+    page: &ErrorPage,
+) -> String {
+    const INDENT: &str = "            ";
+
+    let mut lines: Vec<String> = page
+        .message
+        .iter()
+        .map(|line| {
+            format!("{}<div><span>{}</span></div>", INDENT, escape(line))
+        })
+        .collect();
+    if page.home_link {
+        lines.push(format!(
+            "{}<div><a href='/'>Go to main page</a></div>",
+            INDENT
+        ));
+    }
+
+    template
+        .replace("{{code}}", &code.to_string())
+        .replace("{{title}}", &escape(&page.title))
+        .replace("{{message}}", &lines.join("\n"))
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::paths;
+
+    #[test]
+    fn checked_in_error_pages_render() {
+        let error_pages = ErrorPages::load(
+            &paths::PROJECT_ROOT.join("front-page").join("error_pages"),
+        )
+        .unwrap();
+
+        assert!(error_pages.codes().contains(&404));
+        for (code, page) in error_pages.pages.iter() {
+            let html = render_error_page(&error_pages.template, *code, page);
+            assert!(!html.contains("{{"), "{} is not fully rendered", code);
+            assert!(html.contains(&format!("<p>Error {}</p>", code)));
+        }
+    }
+}
@@ -0,0 +1,277 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Error;
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+
+use crate::core_dist::{
+    DistributionPath, FailurePolicy, OK, TaskResult, to_hex, url_path,
+};
+use crate::web_wasm_loader::WASM_LOADER_FILE;
+
+////////////////////////////////////////////////////////////////////////////////
+// Fingerprinting Settings
+////////////////////////////////////////////////////////////////////////////////
+
+/// Assets renamed to `name.<hash>.ext`, stage by stage: files of a stage may
+/// only reference files of previous stages (css -> font, js -> wasm)
+const FINGERPRINT_STAGES: &[&[&[u8]]] = &[
+    &[b"wasm", b"otf", b"woff2", b"ico", b"png", b"svg"],
+    &[b"css"],
+    &[b"js"],
+];
+
+/// Fingerprinted in a stage of their own after `FINGERPRINT_STAGES` (wasm
+/// loader lists js glue modules)
+const LAST_STAGE_FILES: &[&str] = &[WASM_LOADER_FILE];
+
+/// Files where references to fingerprinted assets are rewritten (web app
+/// manifest lists icons)
+const REWRITE_EXTENSIONS: &[&[u8]] = &[b"html", b"css", b"js", b"webmanifest"];
+
+/// Browsers request these by fixed name on their own, keep them too
+const KEEP_ORIGINAL_FILES: &[&str] = &["favicon.ico", "apple-touch-icon.png"];
+
+pub const FINGERPRINT_MANIFEST_FILE: &str = "manifest.json";
+
+const FINGERPRINT_LENGTH: usize = 16;
+
+////////////////////////////////////////////////////////////////////////////////
+// Fingerprinting
+////////////////////////////////////////////////////////////////////////////////
+
+/// Rename assets in `web_dist_path` by content hash, rewrite references to
+/// them and write `manifest.json` (original path -> fingerprinted path).
+/// References are rewritten by file name, so assets sharing file name (in
+/// different directories) fail the build.
+pub fn fingerprint_web_distribution(web_dist_path: &Path) -> TaskResult {
+    let mut manifest = BTreeMap::new();
+    let mut asset_names = BTreeMap::new();
+    let is_last_stage_file = |path: &Path| {
+        path.to_str()
+            .is_some_and(|path| LAST_STAGE_FILES.contains(&path))
+    };
+
+    for stage in 0..=FINGERPRINT_STAGES.len() {
+        let mut assets = vec![];
+        web_dist_path.for_each_file_recursively(
+            FailurePolicy::FailFast,
+            |relative_path| {
+                let in_stage = match FINGERPRINT_STAGES.get(stage) {
+                    Some(extensions) => {
+                        relative_path.contains_any_extension(extensions)
+                            && !is_last_stage_file(relative_path)
+                    }
+                    None => is_last_stage_file(relative_path),
+                };
+                if in_stage {
+                    assets.push(relative_path.to_owned());
+                }
+                OK
+            },
+        )?;
+        assets.sort();
+
+        for asset in &assets {
+            let name = asset.file_name().unwrap_or_default().to_owned();
+            if let Some(other) = asset_names.insert(name, asset.clone()) {
+                return Err(Error::other(format!(
+                    "{} and {} share file name, references to fingerprinted \
+                     assets are rewritten by file name (rename one of them)",
+                    other.display(),
+                    asset.display()
+                )));
+            }
+        }
+
+        let mut renames = vec![];
+        for asset in assets {
+            let path = web_dist_path.join(&asset);
+            let fingerprinted = fingerprinted_path(&asset, &fs::read(&path)?);
+            let keep_original = asset
+                .to_str()
+                .is_some_and(|asset| KEEP_ORIGINAL_FILES.contains(&asset));
+
+            log::info!(
+                "[xtask] Fingerprinting {} as {}",
+                asset.display(),
+                fingerprinted.display()
+            );
+            if keep_original {
+                fs::copy(&path, web_dist_path.join(&fingerprinted))?;
+            } else {
+                fs::rename(&path, web_dist_path.join(&fingerprinted))?;
+            }
+            renames.push((asset, fingerprinted));
+        }
+
+        // already fingerprinted files must stay as hashed
+        let fingerprinted_files: Vec<_> =
+            manifest.values().map(PathBuf::from).collect();
+        rewrite_references(web_dist_path, &renames, &fingerprinted_files)?;
+
+        manifest.extend(renames.into_iter().map(|(asset, fingerprinted)| {
+            (url_path(&asset), url_path(&fingerprinted))
+        }));
+    }
+
+    let manifest_path = web_dist_path.join(FINGERPRINT_MANIFEST_FILE);
+    log::info!("[xtask] Writing {}", manifest_path.display());
+    fs::write(
+        manifest_path,
+        serde_json::to_string_pretty(&manifest).map_err(Error::other)?,
+    )?;
+
+    OK
+}
+
+/// `dir/name.ext` -> `dir/name.<hash>.ext`
+///
+/// # Examples
+///
+/// ```
+/// use std::path::Path;
+/// use xtask::web_fingerprint::fingerprinted_path;
+///
+/// assert_eq!(
+///     fingerprinted_path(Path::new("fonts/a.otf"), b""),
+///     Path::new("fonts/a.e3b0c44298fc1c14.otf")
+/// );
+/// assert_eq!(
+///     fingerprinted_path(Path::new("a.min.js"), b""),
+///     Path::new("a.min.e3b0c44298fc1c14.js")
+/// );
+/// assert_eq!(
+///     fingerprinted_path(Path::new("LICENSE"), b""),
+///     Path::new("LICENSE.e3b0c44298fc1c14")
+/// );
+/// ```
+pub fn fingerprinted_path(relative_path: &Path, content: &[u8]) -> PathBuf {
+    let hash = to_hex(&Sha256::digest(content));
+    let fingerprint = &hash[..FINGERPRINT_LENGTH];
+
+    let stem = relative_path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy())
+        .unwrap_or_default();
+    let file_name = match relative_path.extension() {
+        Some(ext) => {
+            format!("{}.{}.{}", stem, fingerprint, ext.to_string_lossy())
+        }
+        None => format!("{}.{}", stem, fingerprint),
+    };
+
+    relative_path.with_file_name(file_name)
+}
+
+/// Replace file names of renamed assets in every html/css/js/webmanifest file
+/// (except `skip`).
+/// Only whole file names are replaced: `./a.js`, `"/x/a.js"` but not `ba.js`.
+fn rewrite_references(
+    web_dist_path: &Path,
+    renames: &[(PathBuf, PathBuf)],
+    skip: &[PathBuf],
+) -> TaskResult {
+    let name_renames: Vec<_> = renames
+        .iter()
+        .filter_map(|(from, to)| {
+            Some((
+                from.file_name()?.to_str()?.to_owned(),
+                to.file_name()?.to_str()?.to_owned(),
+            ))
+        })
+        .collect();
+
+    let mut files = vec![];
+    web_dist_path.for_each_file_recursively(
+        FailurePolicy::FailFast,
+        |relative_path| {
+            if relative_path.contains_any_extension(REWRITE_EXTENSIONS)
+                && !skip.iter().any(|skipped| skipped == relative_path)
+            {
+                files.push(relative_path.to_owned());
+            }
+            OK
+        },
+    )?;
+
+    for file in files {
+        let path = web_dist_path.join(&file);
+        let content = fs::read_to_string(&path)?;
+        let rewritten = name_renames
+            .iter()
+            .fold(content.clone(), |text, (from, to)| {
+                replace_file_name(&text, from, to)
+            });
+
+        if rewritten != content {
+            log::debug!("[xtask] Rewriting references in {}", file.display());
+            fs::write(&path, rewritten)?;
+        }
+    }
+
+    OK
+}
+
+/// Replace `from` surrounded by non file name chars.
+///
+/// # Examples
+///
+/// ```
+/// use xtask::web_fingerprint::replace_file_name;
+///
+/// assert_eq!(
+///     replace_file_name("import(\"./a.js\")", "a.js", "a.1.js"),
+///     "import(\"./a.1.js\")"
+/// );
+/// assert_eq!(
+///     replace_file_name("url(/f/a.js) a.js.map ba.js a.js", "a.js", "a.1.js"),
+///     "url(/f/a.1.js) a.js.map ba.js a.1.js"
+/// );
+/// ```
+pub fn replace_file_name(text: &str, from: &str, to: &str) -> String {
+    let is_name_char =
+        |c: char| c.is_alphanumeric() || matches!(c, '_' | '-' | '.');
+    let mut result = String::with_capacity(text.len());
+    let mut last_end = 0;
+
+    for (start, _) in text.match_indices(from) {
+        let end = start + from.len();
+        let bounded_before =
+            !text[..start].chars().next_back().is_some_and(is_name_char);
+        let bounded_after =
+            !text[end..].chars().next().is_some_and(is_name_char);
+
+        if start >= last_end && bounded_before && bounded_after {
+            result.push_str(&text[last_end..start]);
+            result.push_str(to);
+            last_end = end;
+        }
+    }
+
+    result.push_str(&text[last_end..]);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn assets_sharing_file_name_fail_fingerprinting() {
+        let web_dist = tempfile::tempdir().unwrap();
+        for dir in ["a", "b"] {
+            fs::create_dir(web_dist.path().join(dir)).unwrap();
+            fs::write(web_dist.path().join(dir).join("x.css"), dir).unwrap();
+        }
+        fs::write(
+            web_dist.path().join("index.html"),
+            r#"<link href="a/x.css"><link href="b/x.css">"#,
+        )
+        .unwrap();
+
+        let error = fingerprint_web_distribution(web_dist.path()).unwrap_err();
+        assert!(error.to_string().contains("share file name"), "{}", error);
+    }
+}
@@ -0,0 +1,430 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::io::{Error, ErrorKind, Result};
+use std::path::Path;
+use std::sync::LazyLock;
+
+use allsorts::Font;
+use allsorts::binary::read::ReadScope;
+use allsorts::font::MatchingPresentation;
+use allsorts::font_data::FontData;
+use allsorts::subset::{CmapTarget, SubsetProfile, subset};
+use regex::{Captures, Regex};
+
+use crate::core_dist::{
+    DistributionPath, FailurePolicy, OK, TaskResult, url_path,
+};
+use crate::html::{TokenKind, tokenize};
+use crate::paths;
+
+////////////////////////////////////////////////////////////////////////////////
+// Subsetting Settings
+////////////////////////////////////////////////////////////////////////////////
+
+/// Attributes rendered as text (besides text content)
+const TEXT_ATTRIBUTES: &[&str] = &["alt", "title", "placeholder", "value"];
+
+/// Files where `@font-face` urls are rewritten
+const REWRITE_EXTENSIONS: &[&[u8]] = &[b"html", b"css"];
+
+const WOFF2_EXTENSION: &str = "woff2";
+/// Brotli quality of WOFF2 (best)
+const WOFF2_QUALITY: usize = 11;
+
+/// `url(...)` with optional `format(...)` following it
+static FONT_URL: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r#"url\(\s*["']?([^)"'\s]*)["']?\s*\)(?:\s*format\(\s*["'][^"']*["']\s*\))?"#,
+    )
+    .unwrap()
+});
+
+static ENTITY: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"&(?:#(\d+)|#[xX]([0-9a-fA-F]+)|([a-zA-Z]+));").unwrap()
+});
+
+////////////////////////////////////////////////////////////////////////////////
+// Subsetting
+////////////////////////////////////////////////////////////////////////////////
+
+/// Subset fonts of `deploy/font-subset.conf` found in `web_dist_path` to
+/// glyphs used by its pages and store them as WOFF2 (see config for details)
+pub fn subset_web_fonts(web_dist_path: &Path) -> TaskResult {
+    let rules = FontSubsetRules::load(&paths::FONT_SUBSET_PATH)?;
+
+    let mut pages = vec![];
+    let mut rewrite_files = vec![];
+    web_dist_path.for_each_file_recursively(
+        FailurePolicy::FailFast,
+        |relative_path| {
+            if relative_path.contains_any_extension(&[b"html"]) {
+                pages.push(relative_path.to_owned());
+            }
+            if relative_path.contains_any_extension(REWRITE_EXTENSIONS) {
+                rewrite_files.push(relative_path.to_owned());
+            }
+            OK
+        },
+    )?;
+
+    let mut text = BTreeSet::new();
+    for page in pages.iter() {
+        text.extend(page_text(&fs::read_to_string(web_dist_path.join(page))?));
+    }
+
+    let mut renames = vec![];
+    for rule in rules.rules.iter() {
+        let font_path = web_dist_path.join(&rule.path);
+        if !font_path.is_file() {
+            log::warn!("[xtask] Font {} is not in distribution", rule.path);
+            continue;
+        }
+
+        let font = fs::read(&font_path)?;
+        let woff2 = subset_font(&font, &text, &rule.safety).map_err(|e| {
+            Error::new(e.kind(), format!("{}: {}", rule.path, e))
+        })?;
+        let woff2_path = font_path.with_extension(WOFF2_EXTENSION);
+        log::info!(
+            "[xtask] Subsetting {} ({} B) into {} ({} B)",
+            rule.path,
+            font.len(),
+            url_path(
+                woff2_path
+                    .strip_prefix(web_dist_path)
+                    .unwrap_or(&woff2_path)
+            ),
+            woff2.len()
+        );
+        fs::write(&woff2_path, woff2)?;
+        fs::remove_file(&font_path)?;
+        renames.push(font_file_name(&rule.path));
+    }
+
+    for file in rewrite_files {
+        let path = web_dist_path.join(&file);
+        let content = fs::read_to_string(&path)?;
+        let rewritten = rewrite_font_urls(&content, &renames);
+        if rewritten != content {
+            log::debug!("[xtask] Rewriting font urls in {}", file.display());
+            fs::write(&path, rewritten)?;
+        }
+    }
+
+    OK
+}
+
+/// Subset font (OpenType or TrueType) to glyphs of `text` and `safety` and
+/// encode it as WOFF2 (kerning and ligatures are dropped)
+pub fn subset_font(
+    font: &[u8],
+    text: &BTreeSet<char>,
+    safety: &BTreeSet<char>,
+) -> Result<Vec<u8>> {
+    let invalid = |e: &dyn std::fmt::Display| {
+        Error::new(ErrorKind::InvalidData, e.to_string())
+    };
+    let font_data = ReadScope::new(font)
+        .read::<FontData>()
+        .map_err(|e| invalid(&e))?;
+    let mut glyph_font =
+        Font::new(font_data.table_provider(0).map_err(|e| invalid(&e))?)
+            .map_err(|e| invalid(&e))?;
+
+    // .notdef goes first
+    let mut glyph_ids = BTreeSet::from([0]);
+    for c in text.iter().chain(safety.iter()) {
+        let (glyph_id, _) = glyph_font.lookup_glyph_index(
+            *c,
+            MatchingPresentation::NotRequired,
+            None,
+        );
+        if glyph_id != 0 {
+            glyph_ids.insert(glyph_id);
+        } else if text.contains(c) && !c.is_whitespace() {
+            log::warn!("[xtask] Font has no glyph for {:?} used by pages", c);
+        }
+    }
+
+    let glyph_ids: Vec<u16> = glyph_ids.into_iter().collect();
+    let subset_font = subset(
+        &font_data.table_provider(0).map_err(|e| invalid(&e))?,
+        &glyph_ids,
+        &SubsetProfile::Minimal,
+        CmapTarget::Unicode,
+    )
+    .map_err(|e| invalid(&e))?;
+
+    woofwoof::compress(&subset_font, "", WOFF2_QUALITY, true)
+        .ok_or_else(|| invalid(&"WOFF2 encoding failed"))
+}
+
+/// Characters of text content and text attributes of page (scripts and
+/// styles are skipped)
+fn page_text(html: &str) -> BTreeSet<char> {
+    let mut text = BTreeSet::new();
+    for token in tokenize(html) {
+        match &token.kind {
+            TokenKind::Text(content) => {
+                text.extend(decode_entities(content).chars());
+            }
+            TokenKind::StartTag { attributes, .. } => {
+                for attribute in attributes {
+                    if TEXT_ATTRIBUTES.contains(&attribute.name.as_str()) {
+                        text.extend(
+                            decode_entities(
+                                attribute.value.unwrap_or_default(),
+                            )
+                            .chars(),
+                        );
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    text
+}
+
+/// Decode numeric and basic named character references (unknown ones are
+/// kept as is)
+///
+/// # Examples
+///
+/// ```
+/// use xtask::web_font_subset::decode_entities;
+///
+/// assert_eq!(
+///     decode_entities("a &amp; b&#33;&#x2014;&nbsp;"),
+///     "a & b!\u{2014}\u{a0}"
+/// );
+/// assert_eq!(decode_entities("&copy; &"), "&copy; &");
+/// ```
+pub fn decode_entities(text: &str) -> String {
+    ENTITY
+        .replace_all(text, |captures: &Captures| {
+            let decoded = if let Some(decimal) = captures.get(1) {
+                decimal.as_str().parse().ok().and_then(char::from_u32)
+            } else if let Some(hex) = captures.get(2) {
+                u32::from_str_radix(hex.as_str(), 16)
+                    .ok()
+                    .and_then(char::from_u32)
+            } else {
+                match &captures[3] {
+                    "amp" => Some('&'),
+                    "lt" => Some('<'),
+                    "gt" => Some('>'),
+                    "quot" => Some('"'),
+                    "apos" => Some('\''),
+                    "nbsp" => Some('\u{a0}'),
+                    _ => None,
+                }
+            };
+            decoded.map_or_else(|| captures[0].to_owned(), String::from)
+        })
+        .into_owned()
+}
+
+/// Point `url()` of subset fonts (by file name) to WOFF2 ones
+///
+/// # Examples
+///
+/// ```
+/// use xtask::web_font_subset::rewrite_font_urls;
+///
+/// assert_eq!(
+///     rewrite_font_urls(
+///         "src: local('A'), url(\"/fonts/A.otf\") format(\"opentype\");",
+///         &["A.otf".to_owned()]
+///     ),
+///     "src: local('A'), url(\"/fonts/A.woff2\") format(\"woff2\");"
+/// );
+/// assert_eq!(
+///     rewrite_font_urls("url(/fonts/BA.otf)", &["A.otf".to_owned()]),
+///     "url(/fonts/BA.otf)"
+/// );
+/// ```
+pub fn rewrite_font_urls(text: &str, font_file_names: &[String]) -> String {
+    FONT_URL
+        .replace_all(text, |captures: &Captures| {
+            let url = &captures[1];
+            let (directory, file_name) = match url.rsplit_once('/') {
+                Some((directory, file_name)) => {
+                    (format!("{}/", directory), file_name)
+                }
+                None => (String::new(), url),
+            };
+            if !font_file_names.iter().any(|name| name == file_name) {
+                return captures[0].to_owned();
+            }
+
+            let stem = file_name
+                .rsplit_once('.')
+                .map_or(file_name, |(stem, _)| stem);
+            format!(
+                "url(\"{}{}.{}\") format(\"{}\")",
+                directory, stem, WOFF2_EXTENSION, WOFF2_EXTENSION
+            )
+        })
+        .into_owned()
+}
+
+fn font_file_name(path: &str) -> String {
+    path.rsplit('/').next().unwrap_or(path).to_owned()
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// Font Subset Rules
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug)]
+pub struct FontSubsetRule {
+    /// relative to distribution root
+    pub path: String,
+    pub safety: BTreeSet<char>,
+}
+
+/// Rules from `deploy/font-subset.conf` (see it for format)
+#[derive(Debug, Default)]
+pub struct FontSubsetRules {
+    pub rules: Vec<FontSubsetRule>,
+}
+
+impl FontSubsetRules {
+    pub fn load(path: &Path) -> Result<Self> {
+        log::debug!("[xtask] Loading font subset rules {}", path.display());
+        let content = fs::read_to_string(path)?;
+        Self::parse(&content).map_err(|e| {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!("{}:{}", path.display(), e),
+            )
+        })
+    }
+
+    /// Parse font subset rules (errors are prefixed with line number)
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xtask::web_font_subset::FontSubsetRules;
+    ///
+    /// let rules = FontSubsetRules::parse(
+    ///     "# comment\n/fonts/a.otf U+0030-0032 U+00A0 xy\n",
+    /// )
+    /// .unwrap();
+    /// assert_eq!(rules.rules[0].path, "fonts/a.otf");
+    /// assert_eq!(
+    ///     rules.rules[0].safety.iter().collect::<String>(),
+    ///     "012xy\u{a0}"
+    /// );
+    ///
+    /// assert!(FontSubsetRules::parse("fonts/a.otf").is_err());
+    /// assert!(FontSubsetRules::parse("/a.otf U+0032-0030").is_err());
+    /// assert!(FontSubsetRules::parse("/a.otf U+D800").is_err());
+    /// ```
+    pub fn parse(content: &str) -> Result<Self> {
+        let mut rules = vec![];
+
+        for (index, line) in content.lines().enumerate() {
+            let line_error = |message: &str| {
+                Error::new(
+                    ErrorKind::InvalidData,
+                    format!("{}: {}", index + 1, message),
+                )
+            };
+            let trimmed = line.trim();
+
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+
+            let mut fields = trimmed.split_whitespace();
+            let Some(path) =
+                fields.next().and_then(|path| path.strip_prefix('/'))
+            else {
+                return Err(line_error("font path should start with '/'"));
+            };
+
+            let mut safety = BTreeSet::new();
+            for field in fields {
+                let Some(range) = field.strip_prefix("U+") else {
+                    safety.extend(field.chars());
+                    continue;
+                };
+                let (start, end) =
+                    range.split_once('-').unwrap_or((range, range));
+                let code_point = |hex: &str| {
+                    u32::from_str_radix(hex, 16)
+                        .ok()
+                        .filter(|code| char::from_u32(*code).is_some())
+                        .ok_or_else(|| {
+                            line_error(&format!("{} is not code point", field))
+                        })
+                };
+                let (start, end) = (code_point(start)?, code_point(end)?);
+                if start > end {
+                    return Err(line_error(&format!(
+                        "{} is empty range",
+                        field
+                    )));
+                }
+                safety.extend((start..=end).filter_map(char::from_u32));
+            }
+
+            rules.push(FontSubsetRule {
+                path: path.to_owned(),
+                safety,
+            });
+        }
+
+        Ok(FontSubsetRules { rules })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checked_in_font_subsets_by_order_of_magnitude() {
+        let rules = FontSubsetRules::load(&paths::FONT_SUBSET_PATH).unwrap();
+        let text = page_text("<p>MATHT.IN</p><a title='me'>me@matht.in</a>");
+        assert!(text.contains(&'@'));
+        assert!(!text.contains(&'<'));
+
+        for rule in rules.rules.iter() {
+            let font = fs::read(
+                paths::PROJECT_ROOT.join("front-page").join(&rule.path),
+            )
+            .unwrap();
+            let woff2 = subset_font(&font, &text, &rule.safety).unwrap();
+
+            // decodes back into font with glyphs of safety set only
+            let subset = woofwoof::decompress(&woff2).unwrap();
+            let font_data = ReadScope::new(&subset).read::<FontData>().unwrap();
+            let mut subset_font =
+                Font::new(font_data.table_provider(0).unwrap()).unwrap();
+            let mut glyph = |c| {
+                subset_font
+                    .lookup_glyph_index(
+                        c,
+                        MatchingPresentation::NotRequired,
+                        None,
+                    )
+                    .0
+            };
+            assert_ne!(glyph('M'), 0);
+            assert_ne!(glyph('@'), 0);
+            assert_eq!(glyph('\u{e9}'), 0);
+
+            assert!(
+                woff2.len() * 10 < font.len(),
+                "{}: {} of {} B",
+                rule.path,
+                woff2.len(),
+                font.len()
+            );
+        }
+    }
+}
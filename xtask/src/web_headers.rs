@@ -0,0 +1,353 @@
+use std::fs;
+use std::io::{Error, ErrorKind, Result};
+use std::path::{Path, PathBuf};
+
+use regex::Regex;
+
+use crate::core_dist::{OK, TaskResult, make_each_directory};
+use crate::paths;
+
+////////////////////////////////////////////////////////////////////////////////
+// Deploy Config Paths
+////////////////////////////////////////////////////////////////////////////////
+
+pub const NGINX_SUBDIRECTORY: &str = "nginx";
+const NGINX_HTTP_HEADERS_FILE: &str = "headers.http.conf";
+const NGINX_SERVER_HEADERS_FILE: &str = "headers.server.conf";
+
+pub const NGINX_CONF_BANNER: &str =
+    "# Generated by `cargo xtask`, do not edit\n";
+
+/// Header rules written by build, layered over `deploy/headers.conf`
+const GENERATED_RULES_EXTENSION: &str = "headers.conf";
+
+pub const GENERATED_RULES_BANNER: &str = NGINX_CONF_BANNER;
+
+/// Prefix of nginx variables holding per-uri header values
+const NGINX_VARIABLE_PREFIX: &str = "web_dist_header_";
+
+const CONTENT_TYPE: &str = "Content-Type";
+
+////////////////////////////////////////////////////////////////////////////////
+// CLI Tasks
+////////////////////////////////////////////////////////////////////////////////
+
+/// Write nginx snippets applying header rules: one with `map` blocks
+/// (include in `http`) and one with `add_header` directives (include in
+/// `server`)
+pub fn generate_nginx_headers() -> TaskResult {
+    let rules = HeaderRules::load_for_distribution(
+        &paths::BUILD_PATH.join(paths::WEB_DIST_SUBDIRECTORY),
+    )?;
+    let nginx_path = paths::BUILD_PATH.join(NGINX_SUBDIRECTORY);
+
+    make_each_directory(&nginx_path)?;
+
+    for (file, content) in [
+        (NGINX_HTTP_HEADERS_FILE, rules.to_nginx_http_conf()),
+        (NGINX_SERVER_HEADERS_FILE, rules.to_nginx_server_conf()),
+    ] {
+        let path = nginx_path.join(file);
+        log::info!("[xtask] Writing {}", path.display());
+        fs::write(path, format!("{}\n{}", NGINX_CONF_BANNER, content))?;
+    }
+
+    OK
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// Header Rules
+////////////////////////////////////////////////////////////////////////////////
+
+/// Headers applied to every URL path matching glob
+#[derive(Debug)]
+pub struct HeaderRule {
+    pub glob: String,
+    /// anchored regex equivalent of `glob` (same for nginx and dev server)
+    pub pattern: String,
+    pub headers: Vec<(String, String)>,
+    regex: Regex,
+}
+
+/// Ordered header rules (see `deploy/headers.conf` for format)
+#[derive(Debug, Default)]
+pub struct HeaderRules {
+    pub rules: Vec<HeaderRule>,
+}
+
+impl HeaderRules {
+    pub fn load(path: &Path) -> Result<Self> {
+        log::debug!("[xtask] Loading header rules {}", path.display());
+        let content = fs::read_to_string(path)?;
+        Self::parse(&content).map_err(|e| {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!("{}:{}", path.display(), e),
+            )
+        })
+    }
+
+    /// Checked-in rules with rules generated by build of `web_dist_path`
+    /// (if any) on top of them
+    pub fn load_for_distribution(web_dist_path: &Path) -> Result<Self> {
+        let mut rules = Self::load(&paths::HEADER_RULES_PATH)?;
+        let generated_path = generated_header_rules_path(web_dist_path);
+        if generated_path.is_file() {
+            rules.append(Self::load(&generated_path)?);
+        }
+        Ok(rules)
+    }
+
+    /// Put `other` rules after own (so they override)
+    pub fn append(&mut self, mut other: HeaderRules) {
+        self.rules.append(&mut other.rules);
+    }
+
+    /// Parse header rules (errors are prefixed with line number)
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xtask::web_headers::HeaderRules;
+    ///
+    /// let rules = HeaderRules::parse(
+    ///     "# comment\n/**\n  Cache-Control: no-cache\n/*.wasm\n  Cache-Control: max-age=60\n",
+    /// )
+    /// .unwrap();
+    /// assert_eq!(rules.rules.len(), 2);
+    ///
+    /// assert!(HeaderRules::parse("  Cache-Control: no-cache").is_err());
+    /// assert!(HeaderRules::parse("*.wasm").is_err());
+    /// assert!(HeaderRules::parse("/**\n  Cache-Control").is_err());
+    /// ```
+    pub fn parse(content: &str) -> Result<Self> {
+        let mut rules: Vec<HeaderRule> = vec![];
+
+        for (index, line) in content.lines().enumerate() {
+            let line_error = |message: &str| {
+                Error::new(
+                    ErrorKind::InvalidData,
+                    format!("{}: {}", index + 1, message),
+                )
+            };
+            let trimmed = line.trim();
+
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+
+            if !line.starts_with(char::is_whitespace) {
+                if !trimmed.starts_with('/') {
+                    return Err(line_error("glob should start with '/'"));
+                }
+                let pattern = glob_to_regex(trimmed);
+                let regex = Regex::new(&pattern)
+                    .map_err(|e| line_error(&e.to_string()))?;
+                rules.push(HeaderRule {
+                    glob: trimmed.to_owned(),
+                    pattern,
+                    headers: vec![],
+                    regex,
+                });
+                continue;
+            }
+
+            let rule = rules
+                .last_mut()
+                .ok_or_else(|| line_error("header outside of glob section"))?;
+            let (name, value) = trimmed
+                .split_once(':')
+                .ok_or_else(|| line_error("expected `Name: value`"))?;
+            let (name, value) = (name.trim(), value.trim());
+
+            if name.is_empty()
+                || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+            {
+                return Err(line_error("invalid header name"));
+            }
+            // would be treated as variable by nginx
+            if value.contains(['$', '"']) {
+                return Err(line_error("'$' and '\"' are not supported"));
+            }
+
+            rule.headers.push((name.to_owned(), value.to_owned()));
+        }
+
+        Ok(HeaderRules { rules })
+    }
+
+    /// Headers for URL path (all matching rules, later override earlier)
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xtask::web_headers::HeaderRules;
+    ///
+    /// let rules = HeaderRules::parse(
+    ///     "/**\n  Cache-Control: no-cache\n  X-A: a\n/**/*.wasm\n  cache-control: max-age=60\n",
+    /// )
+    /// .unwrap();
+    ///
+    /// assert_eq!(
+    ///     rules.headers_for("/index.html"),
+    ///     [("Cache-Control", "no-cache"), ("X-A", "a")]
+    /// );
+    /// assert_eq!(
+    ///     rules.headers_for("/pkg/a.wasm"),
+    ///     [("Cache-Control", "max-age=60"), ("X-A", "a")]
+    /// );
+    /// ```
+    pub fn headers_for(&self, url_path: &str) -> Vec<(&str, &str)> {
+        let mut headers: Vec<(&str, &str)> = vec![];
+
+        for rule in self.rules.iter().filter(|r| r.regex.is_match(url_path)) {
+            for (name, value) in rule.headers.iter() {
+                match headers
+                    .iter_mut()
+                    .find(|(known, _)| known.eq_ignore_ascii_case(name))
+                {
+                    Some((_, known_value)) => *known_value = value,
+                    None => headers.push((name, value)),
+                }
+            }
+        }
+
+        headers
+    }
+
+    /// `map` blocks (http context) computing each header value from `$uri`
+    pub fn to_nginx_http_conf(&self) -> String {
+        let mut conf = String::new();
+
+        for name in self.header_names() {
+            conf.push_str(&format!("map $uri ${} {{\n", nginx_variable(&name)));
+            // nginx map takes first matching regex, rules take last one
+            for rule in self.rules.iter().rev() {
+                if let Some((_, value)) = rule
+                    .headers
+                    .iter()
+                    .rev()
+                    .find(|(known, _)| known.eq_ignore_ascii_case(&name))
+                {
+                    conf.push_str(&format!(
+                        "    \"~{}\" \"{}\";\n",
+                        rule.pattern, value
+                    ));
+                }
+            }
+            conf.push_str("    default \"\";\n}\n\n");
+        }
+
+        conf
+    }
+
+    /// `add_header` directives and `Content-Type` overrides (server context)
+    pub fn to_nginx_server_conf(&self) -> String {
+        let mut conf = String::new();
+
+        // note: empty value means no header at all
+        for name in self.header_names() {
+            conf.push_str(&format!(
+                "add_header {} ${} always;\n",
+                name,
+                nginx_variable(&name)
+            ));
+        }
+
+        for rule in self.rules.iter() {
+            if let Some((_, content_type)) = rule
+                .headers
+                .iter()
+                .rev()
+                .find(|(name, _)| name.eq_ignore_ascii_case(CONTENT_TYPE))
+            {
+                conf.push_str(&format!(
+                    "\nlocation ~ \"{}\" {{\n    types {{ }}\n    default_type \"{}\";\n}}\n",
+                    rule.pattern, content_type
+                ));
+            }
+        }
+
+        conf
+    }
+
+    /// Unique header names (in order of appearance), except `Content-Type`
+    fn header_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = vec![];
+        self.rules
+            .iter()
+            .flat_map(|rule| rule.headers.iter())
+            .filter(|(name, _)| !name.eq_ignore_ascii_case(CONTENT_TYPE))
+            .for_each(|(name, _)| {
+                if !names.iter().any(|known| known.eq_ignore_ascii_case(name)) {
+                    names.push(name.clone());
+                }
+            });
+        names
+    }
+}
+
+/// Translate URL path glob into anchored regex.
+///
+/// # Examples
+///
+/// ```
+/// use xtask::web_headers::glob_to_regex;
+///
+/// assert_eq!(glob_to_regex("/**"), "^/.*$");
+/// assert_eq!(glob_to_regex("/**/*.html"), r"^/(?:.*/)?[^/]*\.html$");
+/// assert_eq!(glob_to_regex("/a?.{js,css}"), r"^/a[^/]\.(?:js|css)$");
+/// assert_eq!(glob_to_regex("/a+b(1)"), r"^/a\+b\(1\)$");
+/// ```
+pub fn glob_to_regex(glob: &str) -> String {
+    let mut regex = String::from("^");
+    let mut chars = glob.chars().peekable();
+    let mut in_alternatives = false;
+
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                if chars.peek() == Some(&'/') {
+                    chars.next();
+                    regex.push_str("(?:.*/)?");
+                } else {
+                    regex.push_str(".*");
+                }
+            }
+            '*' => regex.push_str("[^/]*"),
+            '?' => regex.push_str("[^/]"),
+            '{' if !in_alternatives => {
+                in_alternatives = true;
+                regex.push_str("(?:");
+            }
+            '}' if in_alternatives => {
+                in_alternatives = false;
+                regex.push(')');
+            }
+            ',' if in_alternatives => regex.push('|'),
+            c if "\\.+()|^$[]{}".contains(c) => {
+                regex.push('\\');
+                regex.push(c);
+            }
+            c => regex.push(c),
+        }
+    }
+
+    regex.push('$');
+    regex
+}
+
+/// Header rules generated by build of `web_dist_path` (stored next to it,
+/// not served)
+pub fn generated_header_rules_path(web_dist_path: &Path) -> PathBuf {
+    web_dist_path.with_extension(GENERATED_RULES_EXTENSION)
+}
+
+fn nginx_variable(header_name: &str) -> String {
+    format!(
+        "{}{}",
+        NGINX_VARIABLE_PREFIX,
+        header_name.to_ascii_lowercase().replace('-', "_")
+    )
+}
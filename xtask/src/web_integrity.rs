@@ -0,0 +1,380 @@
+use std::fs;
+use std::io::Result;
+use std::path::{Path, PathBuf};
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use sha2::{Digest, Sha384};
+
+use crate::core_dist::{
+    DistributionPath, FailurePolicy, OK, TaskResult, url_path,
+};
+use crate::html::{Token, TokenKind, line_number, tokenize};
+use crate::web_headers::{GENERATED_RULES_BANNER, generated_header_rules_path};
+
+////////////////////////////////////////////////////////////////////////////////
+// Policy Settings
+////////////////////////////////////////////////////////////////////////////////
+
+/// `<link rel=...>` kinds which accept `integrity`
+const INTEGRITY_LINK_RELS: &[&str] =
+    &["stylesheet", "modulepreload", "preload"];
+
+/// Directives shared by every page (besides `script-src` and `style-src`)
+const BASE_DIRECTIVES: &[&str] = &[
+    "default-src 'none'",
+    "img-src 'self'",
+    "font-src 'self'",
+    "connect-src 'self'",
+    "manifest-src 'self'",
+    "object-src 'none'",
+    "base-uri 'none'",
+    "form-action 'none'",
+];
+
+/// Directives ignored in `<meta>` (header only)
+const HEADER_ONLY_DIRECTIVES: &[&str] = &["frame-ancestors 'none'"];
+
+const CSP_HEADER: &str = "Content-Security-Policy";
+
+////////////////////////////////////////////////////////////////////////////////
+// Integrity
+////////////////////////////////////////////////////////////////////////////////
+
+/// Add `integrity` to scripts and stylesheets of every page in
+/// `web_dist_path`, put strict per-page CSP (allowing exactly its inline
+/// blocks by hash) into `<meta>` and header rules next to distribution.
+/// Runs on final (minified, fingerprinted) files.
+pub fn secure_web_distribution(web_dist_path: &Path) -> TaskResult {
+    let mut pages = vec![];
+    let mut has_wasm = false;
+    web_dist_path.for_each_file_recursively(
+        FailurePolicy::FailFast,
+        |relative_path| {
+            if relative_path.contains_any_extension(&[b"html"]) {
+                pages.push(relative_path.to_owned());
+            }
+            has_wasm |= relative_path.contains_any_extension(&[b"wasm"]);
+            OK
+        },
+    )?;
+    pages.sort();
+
+    let mut header_rules = String::from(GENERATED_RULES_BANNER);
+    header_rules.push_str("# Content-Security-Policy of built pages\n");
+
+    for page in pages {
+        let path = web_dist_path.join(&page);
+        let html = fs::read_to_string(&path)?;
+        let secured = secure_page(web_dist_path, &page, &html, has_wasm)?;
+
+        log::info!(
+            "[xtask] Securing {} ({} inline, {} external)",
+            page.display(),
+            secured.inline_count,
+            secured.external_count
+        );
+        fs::write(&path, secured.html)?;
+
+        header_rules.push_str(&format!(
+            "\n/{}\n    {}: {}\n",
+            url_path(&page),
+            CSP_HEADER,
+            secured.policy.to_header_value()
+        ));
+    }
+
+    let rules_path = generated_header_rules_path(web_dist_path);
+    log::info!("[xtask] Writing {}", rules_path.display());
+    fs::write(rules_path, header_rules)?;
+
+    OK
+}
+
+/// Subresource Integrity value of content
+///
+/// # Examples
+///
+/// ```
+/// use xtask::web_integrity::integrity;
+///
+/// assert_eq!(
+///     integrity(b"alert('Hello, world.');"),
+///     "sha384-H8BRh8j48O9oYatfu5AZzq6A9RINhZO5H16dQZngK7T62em8MUt1FLm52t+eX6xO"
+/// );
+/// ```
+pub fn integrity(content: &[u8]) -> String {
+    format!("sha384-{}", BASE64.encode(Sha384::digest(content)))
+}
+
+/// Allowed sources of single page
+#[derive(Debug, Default)]
+pub struct PagePolicy {
+    pub script_hashes: Vec<String>,
+    pub style_hashes: Vec<String>,
+    pub has_scripts: bool,
+    pub has_wasm: bool,
+}
+
+impl PagePolicy {
+    /// Policy for `<meta http-equiv="Content-Security-Policy">`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xtask::web_integrity::PagePolicy;
+    ///
+    /// let policy = PagePolicy {
+    ///     style_hashes: vec!["sha384-a".to_owned()],
+    ///     ..Default::default()
+    /// };
+    /// let value = policy.to_meta_value();
+    /// assert!(value.contains("script-src 'none';"));
+    /// assert!(value.contains("style-src 'self' 'sha384-a';"));
+    /// assert!(!value.contains("frame-ancestors"));
+    /// ```
+    pub fn to_meta_value(&self) -> String {
+        self.directives().join("; ")
+    }
+
+    /// Policy for `Content-Security-Policy` header
+    pub fn to_header_value(&self) -> String {
+        let mut directives = self.directives();
+        directives.extend(HEADER_ONLY_DIRECTIVES.iter().map(|d| d.to_string()));
+        directives.join("; ")
+    }
+
+    fn directives(&self) -> Vec<String> {
+        let quoted = |hashes: &[String]| {
+            hashes
+                .iter()
+                .map(|hash| format!(" '{}'", hash))
+                .collect::<String>()
+        };
+
+        let script_src = if self.has_scripts {
+            format!(
+                "script-src 'self'{}{}",
+                quoted(&self.script_hashes),
+                if self.has_wasm {
+                    " 'wasm-unsafe-eval'"
+                } else {
+                    ""
+                }
+            )
+        } else {
+            "script-src 'none'".to_owned()
+        };
+        let style_src =
+            format!("style-src 'self'{}", quoted(&self.style_hashes));
+
+        let mut directives: Vec<String> =
+            BASE_DIRECTIVES.iter().map(|d| d.to_string()).collect();
+        directives.insert(1, script_src);
+        directives.insert(2, style_src);
+        directives
+    }
+}
+
+struct SecuredPage {
+    html: String,
+    policy: PagePolicy,
+    inline_count: usize,
+    external_count: usize,
+}
+
+fn secure_page(
+    web_dist_path: &Path,
+    page: &Path,
+    html: &str,
+    has_wasm: bool,
+) -> Result<SecuredPage> {
+    let tokens = tokenize(html);
+    let mut policy = PagePolicy {
+        has_wasm,
+        ..Default::default()
+    };
+    let mut insertions: Vec<(usize, String)> = vec![];
+    let mut external_count = 0;
+
+    for (index, token) in tokens.iter().enumerate() {
+        let TokenKind::StartTag { attributes, .. } = &token.kind else {
+            continue;
+        };
+
+        for attribute in attributes {
+            if attribute.name == "style" || attribute.name.starts_with("on") {
+                log::warn!(
+                    "[xtask] {}:{}: inline `{}` attribute is blocked by CSP, \
+                     move it to stylesheet or script",
+                    page.display(),
+                    line_number(html, token.span.start),
+                    attribute.name
+                );
+            }
+        }
+
+        let external = match token.tag_name() {
+            Some("script") => {
+                policy.has_scripts = true;
+                match token.attribute("src") {
+                    Some(src) => Some(src),
+                    None => {
+                        let content = inline_content(&tokens, index);
+                        policy
+                            .script_hashes
+                            .push(integrity(content.as_bytes()));
+                        None
+                    }
+                }
+            }
+            Some("style") => {
+                let content = inline_content(&tokens, index);
+                policy.style_hashes.push(integrity(content.as_bytes()));
+                None
+            }
+            Some("link")
+                if token.attribute("rel").is_some_and(|rel| {
+                    rel.split_ascii_whitespace().any(|rel| {
+                        INTEGRITY_LINK_RELS
+                            .iter()
+                            .any(|known| rel.eq_ignore_ascii_case(known))
+                    })
+                }) =>
+            {
+                token.attribute("href")
+            }
+            _ => None,
+        };
+
+        if let Some(url) = external
+            && token.attribute("integrity").is_none()
+            && let Some(file) = resolve_local_url(web_dist_path, page, url)
+        {
+            let hash = integrity(&fs::read(&file)?);
+            insertions.push((
+                token.attribute_insert_offset(html),
+                format!(" integrity=\"{}\"", hash),
+            ));
+            external_count += 1;
+        }
+    }
+
+    insertions.push((
+        csp_meta_offset(&tokens),
+        format!(
+            "<meta http-equiv=\"{}\" content=\"{}\">",
+            CSP_HEADER,
+            policy.to_meta_value()
+        ),
+    ));
+    insertions.sort_by_key(|(offset, _)| *offset);
+
+    let mut secured = String::with_capacity(html.len() + 1024);
+    let mut last_offset = 0;
+    for (offset, insertion) in insertions {
+        secured.push_str(&html[last_offset..offset]);
+        secured.push_str(&insertion);
+        last_offset = offset;
+    }
+    secured.push_str(&html[last_offset..]);
+
+    Ok(SecuredPage {
+        html: secured,
+        inline_count: policy.script_hashes.len() + policy.style_hashes.len(),
+        external_count,
+        policy,
+    })
+}
+
+/// Content of `<script>`/`<style>` opened by token at `index`
+fn inline_content<'a>(tokens: &[Token<'a>], index: usize) -> &'a str {
+    match tokens.get(index + 1).map(|token| &token.kind) {
+        Some(TokenKind::RawText(content)) => content,
+        _ => "",
+    }
+}
+
+/// CSP `<meta>` goes as early as possible (it only applies to elements after
+/// it), but after `<meta charset>`: after leading doctype, comments, `<html>`,
+/// `<head>` and charset declaration (minified pages may omit `<head>`).
+fn csp_meta_offset(tokens: &[Token]) -> usize {
+    let mut offset = 0;
+
+    for token in tokens {
+        let leading = match &token.kind {
+            TokenKind::Doctype | TokenKind::Comment(_) => true,
+            TokenKind::Text(text) => text.trim().is_empty(),
+            TokenKind::StartTag { name, .. } => {
+                name == "html"
+                    || name == "head"
+                    || (name == "meta" && token.attribute("charset").is_some())
+            }
+            _ => false,
+        };
+        if !leading {
+            break;
+        }
+        offset = token.span.end;
+    }
+
+    offset
+}
+
+/// File of same-origin URL referenced by `page` (`None` for external URLs or
+/// missing files)
+fn resolve_local_url(
+    web_dist_path: &Path,
+    page: &Path,
+    url: &str,
+) -> Option<PathBuf> {
+    let url = url.split(['?', '#']).next().unwrap_or_default();
+    if url.is_empty() || url.starts_with("//") || url.contains(':') {
+        return None;
+    }
+
+    let file = match url.strip_prefix('/') {
+        Some(absolute) => web_dist_path.join(absolute),
+        None => web_dist_path
+            .join(page.parent().unwrap_or(Path::new("")))
+            .join(url),
+    };
+
+    file.is_file().then_some(file)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_pins_inline_blocks_and_local_files() {
+        let web_dist = tempfile::tempdir().expect("temp dir");
+        fs::write(web_dist.path().join("a.js"), "let a;").unwrap();
+        let html = "<!doctype html><html lang=en><meta charset=utf-8>\
+            <title>t</title><style>p{}</style>\
+            <script src=./a.js></script><script src=https://x.y/b.js></script>\
+            <script>start()</script>";
+
+        let secured =
+            secure_page(web_dist.path(), Path::new("index.html"), html, true)
+                .unwrap();
+
+        let meta_offset = secured.html.find("<meta http-equiv").unwrap();
+        assert_eq!(meta_offset, html.find("<title>").unwrap());
+        assert!(secured.html.contains(&format!(
+            "<script src=./a.js integrity=\"{}\">",
+            integrity(b"let a;")
+        )));
+        assert!(secured.html.contains("<script src=https://x.y/b.js>"));
+        assert_eq!(secured.external_count, 1);
+
+        let meta = secured.policy.to_meta_value();
+        assert!(meta.contains(&format!("'{}'", integrity(b"p{}"))));
+        assert!(meta.contains(&format!(
+            "'{}' 'wasm-unsafe-eval'",
+            integrity(b"start()")
+        )));
+        assert!(!meta.contains("unsafe-inline"));
+    }
+}
@@ -0,0 +1,436 @@
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use std::fs;
+use std::io::{Error, Result};
+use std::path::Path;
+use std::sync::LazyLock;
+
+use regex::Regex;
+
+use crate::core_dist::{
+    DistributionPath, FailurePolicy, OK, TaskResult, task_flag, url_path,
+};
+use crate::html::{TokenKind, line_number, tokenize};
+use crate::paths;
+use crate::web_dist::{ERROR_PAGE_SUBDIR, HIDDEN_ERROR_PAGE_DIR};
+
+////////////////////////////////////////////////////////////////////////////////
+// Reference Patterns
+////////////////////////////////////////////////////////////////////////////////
+
+/// Attributes holding single URL
+const URL_ATTRIBUTES: &[&str] = &["href", "src"];
+
+/// `url(...)` of css (quoted or not)
+static CSS_URL: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"url\(\s*(?:"([^"]*)"|'([^']*)'|([^)"'\s]*))\s*\)"#).unwrap()
+});
+
+/// String literals of scripts naming script or wasm file by path (targets of
+/// `import()`)
+static JS_MODULE_PATH: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"["'`]((?:\.{1,2})?/[^"'`\s]+\.(?:m?js|wasm))["'`]"#).unwrap()
+});
+
+static MAILTO_ADDRESS: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"^[A-Za-z0-9.!#$%&'*+/=^_`{|}~-]+@[A-Za-z0-9-]+(?:\.[A-Za-z0-9-]+)+$",
+    )
+    .unwrap()
+});
+
+/// `scheme:` of absolute URL
+static URL_SCHEME: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^[A-Za-z][A-Za-z0-9+.-]*:").unwrap());
+
+////////////////////////////////////////////////////////////////////////////////
+// CLI Tasks
+////////////////////////////////////////////////////////////////////////////////
+
+/// Check references of every page (and stylesheet) of last built distribution
+/// (`--dev` for developer one) against its files
+pub fn check_links() -> TaskResult {
+    let web_dist_path = paths::BUILD_PATH.join(if task_flag("--dev") {
+        paths::WEB_DIST_DEV_SUBDIRECTORY
+    } else {
+        paths::WEB_DIST_SUBDIRECTORY
+    });
+
+    let issues = check_distribution_links(&web_dist_path)?;
+    if issues.is_empty() {
+        log::info!(
+            "[xtask] No broken references in {}",
+            web_dist_path.display()
+        );
+        return OK;
+    }
+
+    for issue in issues.iter() {
+        log::error!("[xtask] {}", issue);
+    }
+    Err(Error::other(format!("{} broken references", issues.len())))
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// Link Checking
+////////////////////////////////////////////////////////////////////////////////
+
+/// Broken reference of distribution file
+#[derive(Debug, PartialEq)]
+pub struct LinkIssue {
+    /// URL path of referencing file
+    pub file: String,
+    pub line: usize,
+    pub reference: String,
+    pub problem: String,
+}
+
+impl fmt::Display for LinkIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}: {} ({})",
+            self.file, self.line, self.reference, self.problem
+        )
+    }
+}
+
+/// Resolve `href`/`src` attributes, css `url()` and script module paths of
+/// every html file (css `url()` of every css file) in `web_dist_path`
+/// against its files. Reports missing files, case mismatches, malformed
+/// `mailto:` links and relative references of error pages (served under any
+/// URL).
+pub fn check_distribution_links(
+    web_dist_path: &Path,
+) -> Result<Vec<LinkIssue>> {
+    let mut files = BTreeSet::new();
+    web_dist_path.for_each_file_recursively(
+        FailurePolicy::FailFast,
+        |relative_path| {
+            files.insert(url_path(relative_path));
+            OK
+        },
+    )?;
+    let tree = FileTree::new(files);
+
+    let mut issues = vec![];
+    for file in tree.files.iter() {
+        let is_html = file.ends_with(".html");
+        if !is_html && !file.ends_with(".css") {
+            continue;
+        }
+
+        let text = fs::read_to_string(web_dist_path.join(file))?;
+        let references = if is_html {
+            html_references(&text)
+        } else {
+            css_references(&text, 0)
+        };
+        log::debug!(
+            "[xtask] Checking {} references of {}",
+            references.len(),
+            file
+        );
+
+        for (offset, reference) in references {
+            if let Some(problem) = tree.check(file, &reference) {
+                issues.push(LinkIssue {
+                    file: file.clone(),
+                    line: line_number(&text, offset),
+                    reference,
+                    problem,
+                });
+            }
+        }
+    }
+
+    Ok(issues)
+}
+
+/// URL paths of distribution stylesheets linked (`<link rel="stylesheet">`)
+/// by html files of `web_dist_path`
+pub fn linked_stylesheets(web_dist_path: &Path) -> Result<BTreeSet<String>> {
+    let mut stylesheets = BTreeSet::new();
+    web_dist_path.for_each_file_recursively(
+        FailurePolicy::FailFast,
+        |relative_path| {
+            let file = url_path(relative_path);
+            if !file.ends_with(".html") {
+                return OK;
+            }
+
+            let html = fs::read_to_string(web_dist_path.join(relative_path))?;
+            for token in tokenize(&html) {
+                let is_stylesheet = token.tag_name() == Some("link")
+                    && token.attribute("rel").is_some_and(|rel| {
+                        rel.split_ascii_whitespace()
+                            .any(|kind| kind.eq_ignore_ascii_case("stylesheet"))
+                    });
+                let Some(href) = token.attribute("href").map(str::trim) else {
+                    continue;
+                };
+                if !is_stylesheet
+                    || URL_SCHEME.is_match(href)
+                    || href.starts_with("//")
+                {
+                    continue;
+                }
+
+                let path = href.split(['?', '#']).next().unwrap_or_default();
+                stylesheets.extend(resolve(&file, path));
+            }
+            OK
+        },
+    )?;
+
+    Ok(stylesheets)
+}
+
+/// `(offset, reference)` of `href`/`src` attributes, css `url()` of
+/// `<style>` and `style` attributes, module paths of `<script>`
+fn html_references(html: &str) -> Vec<(usize, String)> {
+    let offset_of =
+        |value: &str| value.as_ptr() as usize - html.as_ptr() as usize;
+    let mut references = vec![];
+    let mut raw_text_element = String::new();
+
+    for token in tokenize(html) {
+        match &token.kind {
+            TokenKind::StartTag {
+                name, attributes, ..
+            } => {
+                raw_text_element = name.clone();
+                for attribute in attributes {
+                    let Some(value) = attribute.value else {
+                        continue;
+                    };
+                    if URL_ATTRIBUTES.contains(&attribute.name.as_str()) {
+                        references.push((
+                            offset_of(value),
+                            value.trim().replace("&amp;", "&"),
+                        ));
+                    } else if attribute.name == "style" {
+                        references
+                            .extend(css_references(value, offset_of(value)));
+                    }
+                }
+            }
+            TokenKind::RawText(text) if raw_text_element == "style" => {
+                references.extend(css_references(text, token.span.start));
+            }
+            TokenKind::RawText(text) if raw_text_element == "script" => {
+                references.extend(JS_MODULE_PATH.captures_iter(text).map(
+                    |captures| {
+                        let path = captures.get(1).unwrap();
+                        (
+                            token.span.start + path.start(),
+                            path.as_str().to_owned(),
+                        )
+                    },
+                ));
+            }
+            _ => {}
+        }
+    }
+
+    references
+}
+
+/// `(offset, reference)` of `url()` in css (`offset` of css itself is added)
+fn css_references(css: &str, offset: usize) -> Vec<(usize, String)> {
+    CSS_URL
+        .captures_iter(css)
+        .filter_map(|captures| {
+            let url = (1..=3).find_map(|group| captures.get(group))?;
+            Some((offset + url.start(), url.as_str().trim().to_owned()))
+        })
+        .collect()
+}
+
+/// Files of distribution (URL paths)
+struct FileTree {
+    files: BTreeSet<String>,
+    /// lowercase path -> path
+    lowercase: HashMap<String, String>,
+}
+
+impl FileTree {
+    fn new(files: BTreeSet<String>) -> Self {
+        let lowercase = files
+            .iter()
+            .map(|file| (file.to_lowercase(), file.clone()))
+            .collect();
+
+        FileTree { files, lowercase }
+    }
+
+    /// Problem of `reference` of `file` (`None` if it resolves)
+    fn check(&self, file: &str, reference: &str) -> Option<String> {
+        if let Some(address) = reference.strip_prefix("mailto:") {
+            return (!is_valid_mailto(address))
+                .then(|| "malformed mailto link".to_owned());
+        }
+        if URL_SCHEME.is_match(reference) || reference.starts_with("//") {
+            // other site (or data:, tel:, ...)
+            return None;
+        }
+
+        let path = reference.split(['?', '#']).next().unwrap_or_default();
+        if path.is_empty() {
+            return None;
+        }
+        let is_error_page = file
+            .starts_with(&format!("{}/", HIDDEN_ERROR_PAGE_DIR))
+            || file.starts_with(&format!("{}/", ERROR_PAGE_SUBDIR));
+        if is_error_page && !path.starts_with('/') {
+            return Some(
+                "relative reference of error page (served under any URL)"
+                    .to_owned(),
+            );
+        }
+
+        let Some(mut resolved) = resolve(file, path) else {
+            return Some("points outside of distribution".to_owned());
+        };
+        if path.ends_with('/') || resolved.is_empty() {
+            resolved = format!("{}index.html", resolved);
+        }
+
+        let index = format!("{}/index.html", resolved);
+        if self.files.contains(&resolved) || self.files.contains(&index) {
+            return None;
+        }
+        if let Some(actual) = self.lowercase.get(&resolved.to_lowercase()) {
+            return Some(format!("case mismatch, file is /{}", actual));
+        }
+        Some(format!("missing /{}", resolved))
+    }
+}
+
+/// URL path of distribution `path` of `file` points to (`None` if it goes
+/// above root)
+///
+/// Directory references end with `/` (empty for root)
+fn resolve(file: &str, path: &str) -> Option<String> {
+    let mut segments: Vec<String> = if path.starts_with('/') {
+        vec![]
+    } else {
+        let mut base: Vec<_> = file.split('/').map(str::to_owned).collect();
+        base.pop();
+        base
+    };
+
+    for segment in path.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop()?;
+            }
+            segment => segments.push(percent_decode(segment)),
+        }
+    }
+
+    let mut resolved = segments.join("/");
+    if path.ends_with('/') && !resolved.is_empty() {
+        resolved.push('/');
+    }
+    Some(resolved)
+}
+
+/// Whether `mailto:` URL (without scheme) has well-formed addresses
+///
+/// # Examples
+///
+/// ```
+/// use xtask::web_links::is_valid_mailto;
+///
+/// assert!(is_valid_mailto("me@matht.in"));
+/// assert!(is_valid_mailto("a@matht.in,b@matht.in?subject=Hi%20there"));
+/// assert!(!is_valid_mailto("me@matht"));
+/// assert!(!is_valid_mailto("me at matht.in"));
+/// assert!(!is_valid_mailto("?subject=Hi"));
+/// ```
+pub fn is_valid_mailto(url: &str) -> bool {
+    let addresses = url.split('?').next().unwrap_or_default();
+    !addresses.is_empty()
+        && addresses
+            .split(',')
+            .all(|address| MAILTO_ADDRESS.is_match(&percent_decode(address)))
+}
+
+/// Decode `%XX` escapes (invalid ones are kept as is)
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut position = 0;
+
+    while position < bytes.len() {
+        let escaped = (bytes[position] == b'%')
+            .then(|| text.get(position + 1..position + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                position += 3;
+            }
+            None => {
+                decoded.push(bytes[position]);
+                position += 1;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_reports_broken_references_of_distribution() {
+        let dir = tempfile::tempdir().unwrap();
+        let web_dist = dir.path();
+        fs::create_dir_all(web_dist.join("fonts")).unwrap();
+        fs::create_dir_all(web_dist.join(HIDDEN_ERROR_PAGE_DIR)).unwrap();
+        fs::write(web_dist.join("fonts/Regular.otf"), "").unwrap();
+        fs::write(web_dist.join("shards_browser.js"), "").unwrap();
+        fs::write(
+            web_dist.join("index.html"),
+            r##"<link rel="icon" href="/favicon.ico">
+<style>@font-face { src: url("/fonts/regular.otf") }</style>
+<a href="mailto:me@matht.in">me</a> <a href="mailto:me@">me</a>
+<a href="https://matht.in/">site</a> <a href="#top">top</a>
+<script>
+const VARIANTS = ["./shards_browser_simd.js"];
+import("./shards_browser.js");
+</script>"##,
+        )
+        .unwrap();
+        fs::write(
+            web_dist.join(HIDDEN_ERROR_PAGE_DIR).join("404.html"),
+            "<a href='/'>home</a><img src='../fonts/Regular.otf'>",
+        )
+        .unwrap();
+
+        let issues: Vec<_> = check_distribution_links(web_dist)
+            .unwrap()
+            .into_iter()
+            .map(|issue| {
+                format!("{}:{} {}", issue.file, issue.line, issue.reference)
+            })
+            .collect();
+
+        assert_eq!(
+            issues,
+            [
+                ".error_pages/404.html:1 ../fonts/Regular.otf",
+                "index.html:1 /favicon.ico",
+                "index.html:2 /fonts/regular.otf",
+                "index.html:3 mailto:me@",
+                "index.html:6 ./shards_browser_simd.js",
+            ]
+        );
+    }
+}
@@ -0,0 +1,696 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::fs;
+use std::io::Error;
+use std::sync::LazyLock;
+
+use regex::Regex;
+
+use crate::core_dist::{
+    DistributionPath, FailurePolicy, OK, TaskResult, url_path,
+};
+use crate::html::{Token, TokenKind, line_number, tokenize};
+use crate::web_dist::front_page_path;
+use crate::web_template::{PARTIALS_SUBDIR, Template};
+
+////////////////////////////////////////////////////////////////////////////////
+// Lint Settings
+////////////////////////////////////////////////////////////////////////////////
+
+/// Elements without end tag
+const VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta",
+    "source", "track", "wbr",
+];
+
+/// Elements which may be closed implicitly
+const OPTIONAL_END_ELEMENTS: &[&str] = &[
+    "html", "head", "body", "p", "li", "dt", "dd", "option", "tr", "td", "th",
+];
+
+/// Elements focusable and announced by themselves
+const INTERACTIVE_ELEMENTS: &[&str] =
+    &["button", "input", "select", "textarea", "summary"];
+
+/// Elements which need text alternative
+const ALT_ELEMENTS: &[&str] = &["img", "area"];
+
+/// WCAG AA contrast of normal text
+const MIN_CONTRAST_RATIO: f64 = 4.5;
+
+/// Keyboard events that make click handler operable without pointer
+const KEYBOARD_EVENTS: &[&str] = &["keydown", "keyup", "keypress"];
+
+/// Comments of css (and directives of templates kept in it)
+static CSS_COMMENT: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?s)/\*.*?\*/|<!--.*?-->").unwrap());
+
+/// Innermost `selector { declarations }` (inside of `@media` as well)
+static CSS_RULE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"([^{}]+)\{([^{}]*)\}").unwrap());
+
+static CSS_DECLARATION: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"([\w-]+)\s*:\s*([^;]+)").unwrap());
+
+/// `name = document.getElementById("id")`
+static JS_ELEMENT_BINDING: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r#"(\w+)\s*=\s*document\.getElementById\(\s*["']([^"']+)["']\s*\)"#,
+    )
+    .unwrap()
+});
+
+/// `name.addEventListener("event"` or `name.onevent =` (`name` may be
+/// `document.getElementById("id")` as well)
+static JS_LISTENER: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r#"(\w+|document\.getElementById\(\s*["'][^"']+["']\s*\))\s*\.\s*(?:addEventListener\(\s*["'](\w+)["']|on(\w+)\s*=[^=])"#,
+    )
+    .unwrap()
+});
+
+////////////////////////////////////////////////////////////////////////////////
+// CLI Tasks
+////////////////////////////////////////////////////////////////////////////////
+
+/// Lint html pages of front-page (sources, not built distribution)
+pub fn lint_front_page() -> TaskResult {
+    let root = front_page_path();
+    let template = Template::for_front_page(&root, true)?;
+
+    let mut pages = vec![];
+    root.for_each_file_recursively(FailurePolicy::FailFast, |relative_path| {
+        if relative_path.contains_any_extension(&[b"html"])
+            && !relative_path.starts_with(PARTIALS_SUBDIR)
+        {
+            pages.push(relative_path.to_owned());
+        }
+        OK
+    })?;
+    pages.sort();
+
+    let mut issues = vec![];
+    for page in pages.iter() {
+        let source = url_path(page);
+        let html = template
+            .expand_variables(&fs::read_to_string(root.join(page))?, &source)?;
+        issues.extend(lint_page(&source, &html));
+    }
+
+    if issues.is_empty() {
+        log::info!("[xtask] {} front-page pages are clean", pages.len());
+        return OK;
+    }
+
+    for issue in issues.iter() {
+        log::error!("[xtask] {}", issue);
+    }
+    Err(Error::other(format!("{} lint violations", issues.len())))
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// Page Lints
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, PartialEq)]
+pub struct LintIssue {
+    pub file: String,
+    pub line: usize,
+    pub rule: &'static str,
+    pub message: String,
+}
+
+impl fmt::Display for LintIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}: {}: {}",
+            self.file, self.line, self.rule, self.message
+        )
+    }
+}
+
+/// Lint violations of page (`file` names it in issues)
+pub fn lint_page(file: &str, html: &str) -> Vec<LintIssue> {
+    let tokens = tokenize(html);
+    let mut lint = PageLint {
+        file,
+        html,
+        issues: vec![],
+    };
+
+    lint.check_structure(&tokens);
+    lint.check_document(&tokens);
+    lint.check_contrast(&tokens);
+    lint.check_click_handlers(&tokens);
+
+    lint.issues.sort_by_key(|issue| issue.line);
+    lint.issues
+}
+
+struct PageLint<'a> {
+    file: &'a str,
+    html: &'a str,
+    issues: Vec<LintIssue>,
+}
+
+impl PageLint<'_> {
+    fn report(&mut self, offset: usize, rule: &'static str, message: String) {
+        self.issues.push(LintIssue {
+            file: self.file.to_owned(),
+            line: line_number(self.html, offset),
+            rule,
+            message,
+        });
+    }
+
+    /// Duplicated attributes, unclosed and stray tags
+    fn check_structure(&mut self, tokens: &[Token]) {
+        let mut open: Vec<(&str, usize)> = vec![];
+
+        for token in tokens {
+            match &token.kind {
+                TokenKind::StartTag {
+                    name,
+                    attributes,
+                    self_closing,
+                } => {
+                    let mut seen = BTreeSet::new();
+                    for attribute in attributes {
+                        if !seen.insert(attribute.name.as_str()) {
+                            self.report(
+                                token.span.start,
+                                "duplicate-attribute",
+                                format!(
+                                    "<{}> has `{}` more than once",
+                                    name, attribute.name
+                                ),
+                            );
+                        }
+                    }
+                    if !self_closing && !VOID_ELEMENTS.contains(&name.as_str())
+                    {
+                        open.push((name, token.span.start));
+                    }
+                }
+                TokenKind::EndTag { name } => {
+                    let Some(position) = open
+                        .iter()
+                        .rposition(|(open_name, _)| open_name == name)
+                    else {
+                        self.report(
+                            token.span.start,
+                            "stray-end-tag",
+                            format!("</{}> closes nothing", name),
+                        );
+                        continue;
+                    };
+                    for (unclosed, offset) in open.split_off(position + 1) {
+                        self.report_unclosed(unclosed, offset);
+                    }
+                    open.pop();
+                }
+                _ => {}
+            }
+        }
+
+        for (unclosed, offset) in open {
+            self.report_unclosed(unclosed, offset);
+        }
+    }
+
+    fn report_unclosed(&mut self, name: &str, offset: usize) {
+        if !OPTIONAL_END_ELEMENTS.contains(&name) {
+            self.report(
+                offset,
+                "unclosed-tag",
+                format!("<{}> is not closed", name),
+            );
+        }
+    }
+
+    /// `lang` of `<html>`, single non-empty `<title>`, `alt` of images
+    fn check_document(&mut self, tokens: &[Token]) {
+        match tokens.iter().find(|token| token.is_start_tag("html")) {
+            Some(html)
+                if html
+                    .attribute("lang")
+                    .is_some_and(|lang| !lang.trim().is_empty()) => {}
+            Some(html) => self.report(
+                html.span.start,
+                "html-lang",
+                "<html> has no `lang`".to_owned(),
+            ),
+            None => {
+                self.report(0, "html-lang", "page has no <html>".to_owned())
+            }
+        }
+
+        let titles: Vec<_> = tokens
+            .iter()
+            .enumerate()
+            .filter(|(_, token)| token.is_start_tag("title"))
+            .collect();
+        if titles.is_empty() {
+            self.report(0, "title", "page has no <title>".to_owned());
+        }
+        for (count, (index, title)) in titles.iter().enumerate() {
+            let is_empty = match tokens.get(index + 1).map(|token| &token.kind)
+            {
+                Some(TokenKind::Text(text)) => text.trim().is_empty(),
+                _ => true,
+            };
+            if count > 0 {
+                self.report(
+                    title.span.start,
+                    "title",
+                    "page has more than one <title>".to_owned(),
+                );
+            } else if is_empty {
+                self.report(
+                    title.span.start,
+                    "title",
+                    "<title> is empty".to_owned(),
+                );
+            }
+        }
+
+        for token in tokens.iter() {
+            let needs_alt = ALT_ELEMENTS
+                .iter()
+                .any(|element| token.is_start_tag(element))
+                || (token.is_start_tag("input")
+                    && token.attribute("type") == Some("image"));
+            if needs_alt && token.attribute("alt").is_none() {
+                self.report(
+                    token.span.start,
+                    "alt",
+                    format!(
+                        "<{}> has no `alt`",
+                        token.tag_name().unwrap_or_default()
+                    ),
+                );
+            }
+        }
+    }
+
+    /// Contrast of text color declared by css rule against background of
+    /// the same rule (page background of `html`/`body` otherwise), and of
+    /// page text color against backgrounds of rules without color
+    fn check_contrast(&mut self, tokens: &[Token]) {
+        let rules: Vec<_> = style_texts(tokens)
+            .into_iter()
+            .flat_map(|(offset, css)| css_rules(css, offset))
+            .collect();
+        let is_page_rule =
+            |rule: &CssRule| rule.selector == "body" || rule.selector == "html";
+        let page_background = rules
+            .iter()
+            .filter(|rule| is_page_rule(rule))
+            .find_map(|rule| rule.background)
+            .unwrap_or(WHITE);
+        let page_color = rules
+            .iter()
+            .filter(|rule| is_page_rule(rule))
+            .find_map(|rule| rule.color);
+
+        for rule in rules.iter() {
+            let (color, background) = match (rule.color, rule.background) {
+                (Some(color), Some(background)) => (color, background),
+                (Some(color), None) => (color, page_background),
+                (None, Some(background)) if !is_page_rule(rule) => {
+                    match page_color {
+                        Some(color) => (color, background),
+                        None => continue,
+                    }
+                }
+                _ => continue,
+            };
+
+            let ratio = contrast_ratio(color, background);
+            if ratio < MIN_CONTRAST_RATIO {
+                self.report(
+                    rule.offset,
+                    "contrast",
+                    format!(
+                        "`{}`: text {} on {} has contrast {:.2} (at least {} required)",
+                        rule.selector,
+                        to_css_hex(color),
+                        to_css_hex(background),
+                        ratio,
+                        MIN_CONTRAST_RATIO
+                    ),
+                );
+            }
+        }
+    }
+
+    /// Elements with click handlers (`on*` attributes or listeners added by
+    /// inline scripts) must be focusable, announced and operable by keyboard
+    fn check_click_handlers(&mut self, tokens: &[Token]) {
+        let mut events: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+
+        for token in tokens {
+            if let Some(id) = token.attribute("id") {
+                let TokenKind::StartTag { attributes, .. } = &token.kind else {
+                    continue;
+                };
+                for attribute in attributes {
+                    if let Some(event) = attribute.name.strip_prefix("on") {
+                        events
+                            .entry(id.to_owned())
+                            .or_default()
+                            .insert(event.to_owned());
+                    }
+                }
+            }
+        }
+        for script in script_texts(tokens) {
+            let bindings: BTreeMap<&str, &str> = JS_ELEMENT_BINDING
+                .captures_iter(script)
+                .map(|captures| {
+                    (
+                        captures.get(1).unwrap().as_str(),
+                        captures.get(2).unwrap().as_str(),
+                    )
+                })
+                .collect();
+            for captures in JS_LISTENER.captures_iter(script) {
+                let target = captures.get(1).unwrap().as_str();
+                let id = match JS_ELEMENT_BINDING
+                    .captures(&format!("_ = {}", target))
+                {
+                    Some(binding) => {
+                        binding.get(2).unwrap().as_str().to_owned()
+                    }
+                    None => match bindings.get(target) {
+                        Some(id) => id.to_string(),
+                        None => continue,
+                    },
+                };
+                let event = captures.get(2).or(captures.get(3)).unwrap();
+                events
+                    .entry(id)
+                    .or_default()
+                    .insert(event.as_str().to_owned());
+            }
+        }
+
+        for token in tokens {
+            let Some(id) = token.attribute("id") else {
+                continue;
+            };
+            let Some(element_events) = events.get(id) else {
+                continue;
+            };
+            let tag = token.tag_name().unwrap_or_default();
+            let is_interactive = INTERACTIVE_ELEMENTS.contains(&tag)
+                || (tag == "a" && token.attribute("href").is_some());
+            if !element_events.contains("click") || is_interactive {
+                continue;
+            }
+
+            let mut missing = vec![];
+            if token
+                .attribute("tabindex")
+                .and_then(|tabindex| tabindex.trim().parse::<i32>().ok())
+                .is_none_or(|tabindex| tabindex < 0)
+            {
+                missing.push("tabindex=\"0\" (focus)");
+            }
+            if token.attribute("role").is_none() {
+                missing.push("role=\"button\" (announcement)");
+            }
+            if !KEYBOARD_EVENTS
+                .iter()
+                .any(|event| element_events.contains(*event))
+            {
+                missing.push("keydown handler (activation)");
+            }
+
+            if !missing.is_empty() {
+                self.report(
+                    token.span.start,
+                    "keyboard",
+                    format!(
+                        "<{} id=\"{}\"> handles clicks but lacks {}",
+                        tag,
+                        id,
+                        missing.join(", ")
+                    ),
+                );
+            }
+        }
+    }
+}
+
+/// `(offset, css)` of `<style>` elements
+fn style_texts<'a>(tokens: &[Token<'a>]) -> Vec<(usize, &'a str)> {
+    raw_texts(tokens, "style")
+        .into_iter()
+        .map(|(token, text)| (token.span.start, text))
+        .collect()
+}
+
+fn script_texts<'a>(tokens: &[Token<'a>]) -> Vec<&'a str> {
+    raw_texts(tokens, "script")
+        .into_iter()
+        .map(|(_, text)| text)
+        .collect()
+}
+
+fn raw_texts<'a, 't>(
+    tokens: &'t [Token<'a>],
+    element: &str,
+) -> Vec<(&'t Token<'a>, &'a str)> {
+    tokens
+        .windows(2)
+        .filter(|pair| pair[0].is_start_tag(element))
+        .filter_map(|pair| match pair[1].kind {
+            TokenKind::RawText(text) => Some((&pair[1], text)),
+            _ => None,
+        })
+        .collect()
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// Colors
+////////////////////////////////////////////////////////////////////////////////
+
+type Rgb = [u8; 3];
+
+const WHITE: Rgb = [255, 255, 255];
+
+const NAMED_COLORS: &[(&str, Rgb)] = &[
+    ("black", [0, 0, 0]),
+    ("white", WHITE),
+    ("gray", [128, 128, 128]),
+    ("grey", [128, 128, 128]),
+    ("silver", [192, 192, 192]),
+    ("red", [255, 0, 0]),
+    ("green", [0, 128, 0]),
+    ("blue", [0, 0, 255]),
+    ("yellow", [255, 255, 0]),
+];
+
+/// Colors declared by css rule (only opaque ones, `inherit` and the like are
+/// ignored)
+struct CssRule {
+    selector: String,
+    offset: usize,
+    color: Option<Rgb>,
+    background: Option<Rgb>,
+}
+
+fn css_rules(css: &str, offset: usize) -> Vec<CssRule> {
+    // keep offsets: comments are blanked, not removed
+    let css = CSS_COMMENT.replace_all(css, |captures: &regex::Captures| {
+        captures[0].replace(|c: char| c != '\n', " ")
+    });
+
+    CSS_RULE
+        .captures_iter(&css)
+        .filter_map(|captures| {
+            let selector = captures.get(1).unwrap();
+            if selector.as_str().trim_start().starts_with('@') {
+                return None;
+            }
+
+            let mut rule = CssRule {
+                selector: selector.as_str().trim().to_owned(),
+                offset: offset
+                    + selector.start()
+                    + (selector.as_str().len()
+                        - selector.as_str().trim_start().len()),
+                color: None,
+                background: None,
+            };
+            for declaration in
+                CSS_DECLARATION.captures_iter(captures.get(2).unwrap().as_str())
+            {
+                let value = declaration[2].trim();
+                match &declaration[1] {
+                    "color" => rule.color = parse_color(value),
+                    "background-color" => rule.background = parse_color(value),
+                    "background" => {
+                        rule.background =
+                            value.split_whitespace().find_map(parse_color)
+                    }
+                    _ => {}
+                }
+            }
+            Some(rule)
+        })
+        .collect()
+}
+
+/// Opaque css color (`#rgb`, `#rrggbb`, `rgb(r, g, b)` or basic name)
+///
+/// # Examples
+///
+/// ```
+/// use xtask::web_lint::parse_color;
+///
+/// assert_eq!(parse_color("#cccccc"), Some([204, 204, 204]));
+/// assert_eq!(parse_color("#FFF"), Some([255, 255, 255]));
+/// assert_eq!(parse_color("rgb(0, 128, 255)"), Some([0, 128, 255]));
+/// assert_eq!(parse_color("black !important"), Some([0, 0, 0]));
+/// assert_eq!(parse_color("transparent"), None);
+/// assert_eq!(parse_color("#00000080"), None);
+/// ```
+pub fn parse_color(value: &str) -> Option<Rgb> {
+    let value = value.trim_end_matches("!important").trim().to_lowercase();
+
+    if let Some(hex) = value.strip_prefix('#') {
+        let digits: Vec<u8> = hex
+            .chars()
+            .map(|c| c.to_digit(16).map(|digit| digit as u8))
+            .collect::<Option<_>>()?;
+        return match digits.len() {
+            3 => Some([digits[0] * 17, digits[1] * 17, digits[2] * 17]),
+            6 => Some([
+                digits[0] * 16 + digits[1],
+                digits[2] * 16 + digits[3],
+                digits[4] * 16 + digits[5],
+            ]),
+            _ => None,
+        };
+    }
+
+    if let Some(arguments) = value
+        .strip_prefix("rgb(")
+        .and_then(|value| value.strip_suffix(')'))
+    {
+        let channels: Vec<u8> = arguments
+            .split([',', ' '])
+            .filter(|channel| !channel.is_empty())
+            .map(|channel| channel.parse().ok())
+            .collect::<Option<_>>()?;
+        return channels.try_into().ok();
+    }
+
+    NAMED_COLORS
+        .iter()
+        .find(|(name, _)| *name == value)
+        .map(|(_, rgb)| *rgb)
+}
+
+/// WCAG contrast ratio of two colors (1 to 21)
+///
+/// # Examples
+///
+/// ```
+/// use xtask::web_lint::contrast_ratio;
+///
+/// assert_eq!(contrast_ratio([0, 0, 0], [255, 255, 255]), 21.0);
+/// assert_eq!(contrast_ratio([128, 128, 128], [128, 128, 128]), 1.0);
+/// ```
+pub fn contrast_ratio(first: Rgb, second: Rgb) -> f64 {
+    let luminance = |rgb: Rgb| {
+        let channel = |value: u8| {
+            let value = value as f64 / 255.0;
+            if value <= 0.04045 {
+                value / 12.92
+            } else {
+                ((value + 0.055) / 1.055).powf(2.4)
+            }
+        };
+        0.2126 * channel(rgb[0])
+            + 0.7152 * channel(rgb[1])
+            + 0.0722 * channel(rgb[2])
+    };
+
+    let (first, second) = (luminance(first), luminance(second));
+    (first.max(second) + 0.05) / (first.min(second) + 0.05)
+}
+
+fn to_css_hex(rgb: Rgb) -> String {
+    format!("#{:02x}{:02x}{:02x}", rgb[0], rgb[1], rgb[2])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_reports_violations_by_line() {
+        let html = r#"<!DOCTYPE html>
+<html>
+<head><title></title>
+<style>
+body { background-color: #202020; color: #eeeeee; }
+.block { background: #5c5c5c; }
+.dim { color: #333; }
+</style>
+</head>
+<body>
+<div id="title" class="a" class="b"><span>T</div>
+<img src="/a.png">
+<script>
+let button = document.getElementById("title");
+button.addEventListener("click", () => {});
+</script>
+</body>
+</html>"#;
+
+        let issues: Vec<_> = lint_page("index.html", html)
+            .into_iter()
+            .map(|issue| format!("{} {}", issue.line, issue.rule))
+            .collect();
+
+        assert_eq!(
+            issues,
+            [
+                "2 html-lang",
+                "3 title",
+                "7 contrast",
+                "11 duplicate-attribute",
+                "11 unclosed-tag",
+                "11 keyboard",
+                "12 alt",
+            ]
+        );
+    }
+
+    #[test]
+    fn checked_in_front_page_has_only_known_violations() {
+        let root = front_page_path();
+        let template = Template::for_front_page(&root, true).unwrap();
+        let rules_of = |page: &str| {
+            let html = template
+                .expand_variables(
+                    &fs::read_to_string(root.join(page)).unwrap(),
+                    page,
+                )
+                .unwrap();
+            let mut rules: Vec<_> =
+                lint_page(page, &html).into_iter().map(|i| i.rule).collect();
+            rules.dedup();
+            rules
+        };
+
+        assert_eq!(rules_of("index.html"), Vec::<&str>::new());
+        // dark text of error pages is kept by design (for now)
+        assert_eq!(rules_of("error_pages/template.html"), ["contrast"]);
+    }
+}
@@ -0,0 +1,149 @@
+use std::fs;
+use std::io::Result;
+use std::path::Path;
+
+use crate::core_dist::{
+    OK, TaskResult, make_each_directory, task_flag, task_option,
+};
+use crate::paths;
+use crate::web_dist::{
+    ERROR_PAGE_SUBDIR, HIDDEN_ERROR_PAGE_DIR, front_page_path,
+};
+use crate::web_error_pages::ErrorPages;
+use crate::web_headers::{HeaderRules, NGINX_CONF_BANNER, NGINX_SUBDIRECTORY};
+
+////////////////////////////////////////////////////////////////////////////////
+// Deploy Defaults
+////////////////////////////////////////////////////////////////////////////////
+
+const NGINX_CONF_FILE: &str = "web-dist.conf";
+
+const DEFAULT_SERVER_NAME: &str = "matht.in";
+const DEFAULT_ROOT: &str = "/var/www/matht.in";
+const DEFAULT_LISTEN: &str = "80";
+
+/// Omits `brotli_static` (for nginx built without ngx_brotli module)
+const NO_BROTLI_FLAG: &str = "--no-brotli";
+
+////////////////////////////////////////////////////////////////////////////////
+// CLI Tasks
+////////////////////////////////////////////////////////////////////////////////
+
+/// Write complete nginx config (include in `http` context).
+/// Accepts `--server-name`, `--root` and `--listen` overrides and
+/// `--no-brotli` flag.
+pub fn generate_nginx_conf() -> TaskResult {
+    let settings = NginxSettings {
+        server_name: task_option("--server-name")
+            .unwrap_or_else(|| DEFAULT_SERVER_NAME.to_owned()),
+        root: task_option("--root").unwrap_or_else(|| DEFAULT_ROOT.to_owned()),
+        listen: task_option("--listen")
+            .unwrap_or_else(|| DEFAULT_LISTEN.to_owned()),
+        brotli: !task_flag(NO_BROTLI_FLAG),
+    };
+    let header_rules = HeaderRules::load_for_distribution(
+        &paths::BUILD_PATH.join(paths::WEB_DIST_SUBDIRECTORY),
+    )?;
+    let error_codes =
+        error_page_codes(&front_page_path().join(ERROR_PAGE_SUBDIR))?;
+
+    let nginx_path = paths::BUILD_PATH.join(NGINX_SUBDIRECTORY);
+    make_each_directory(&nginx_path)?;
+
+    let conf_path = nginx_path.join(NGINX_CONF_FILE);
+    log::info!("[xtask] Writing {}", conf_path.display());
+    fs::write(
+        conf_path,
+        render_nginx_conf(&settings, &header_rules, &error_codes),
+    )?;
+
+    OK
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// Rendering
+////////////////////////////////////////////////////////////////////////////////
+
+pub struct NginxSettings {
+    pub server_name: String,
+    pub root: String,
+    pub listen: String,
+    /// Serve `.br` siblings (requires ngx_brotli module)
+    pub brotli: bool,
+}
+
+/// HTTP status codes of error pages rendered from `dir` (sorted)
+pub fn error_page_codes(dir: &Path) -> Result<Vec<u16>> {
+    Ok(ErrorPages::load(dir)?.codes())
+}
+
+/// Render nginx config: header `map`s and server block serving web-dist
+pub fn render_nginx_conf(
+    settings: &NginxSettings,
+    header_rules: &HeaderRules,
+    error_codes: &[u16],
+) -> String {
+    let mut conf = String::from(NGINX_CONF_BANNER);
+
+    conf.push_str("\n# Response headers (deploy/headers.conf and build)\n\n");
+    conf.push_str(&header_rules.to_nginx_http_conf());
+
+    conf.push_str(&format!(
+        "server {{
+    listen {listen};
+    server_name {server_name};
+
+    root {root};
+    index index.html;
+    charset utf-8;
+
+    # serve pre-compressed siblings (*.gz, *.br) of requested files
+    gzip_static on;
+{brotli_static}
+    location / {{
+        try_files $uri $uri/ =404;
+    }}
+
+    # error pages are reachable only through error_page
+    location ^~ /{error_dir}/ {{
+        internal;
+    }}
+
+",
+        listen = settings.listen,
+        server_name = settings.server_name,
+        root = settings.root,
+        error_dir = HIDDEN_ERROR_PAGE_DIR,
+        brotli_static = if settings.brotli {
+            "    # requires ngx_brotli module (omit with --no-brotli)\n    \
+             brotli_static on;\n"
+        } else {
+            ""
+        },
+    ));
+
+    for code in error_codes {
+        conf.push_str(&format!(
+            "    error_page {code} /{}/{code}.html;\n",
+            HIDDEN_ERROR_PAGE_DIR
+        ));
+    }
+
+    conf.push('\n');
+    conf.push_str(&indent(&header_rules.to_nginx_server_conf()));
+    conf.push_str("}\n");
+
+    conf
+}
+
+fn indent(text: &str) -> String {
+    text.lines()
+        .map(|line| {
+            if line.is_empty() {
+                "\n".to_owned()
+            } else {
+                format!("    {}\n", line)
+            }
+        })
+        .collect()
+}
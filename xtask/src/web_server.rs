@@ -0,0 +1,527 @@
+use std::fs;
+use std::io::{BufRead, BufReader, Result, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex, mpsc};
+use std::thread;
+use std::time::Duration;
+
+use crate::core_dist::{OK, TaskResult, url_path};
+use crate::paths;
+use crate::web_dist::{
+    ERROR_PAGE_SUBDIR, HIDDEN_ERROR_PAGE_DIR, build_web_distribution,
+};
+use crate::web_headers::HeaderRules;
+use crate::web_links::linked_stylesheets;
+use crate::web_watch::{WatchChanges, watch_web_distribution_by_path};
+
+////////////////////////////////////////////////////////////////////////////////
+// Server Settings
+////////////////////////////////////////////////////////////////////////////////
+
+const SERVE_ADDRESS: &str = "127.0.0.1:8080";
+
+const INDEX_FILE: &str = "index.html";
+
+const NOT_FOUND_PAGE: &str = "404.html";
+
+pub const LIVE_RELOAD_ENDPOINT: &str = "/__xtask/live-reload";
+
+/// Keeps idle SSE connections alive and lets dead ones be noticed
+const LIVE_RELOAD_PING_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Client side of live reload, injected into served HTML (dev only).
+/// Stylesheet changes re-fetch `<link rel="stylesheet">`, anything else
+/// reloads the page.
+const LIVE_RELOAD_SCRIPT: &str = r#"<script>
+(() => {
+    const source = new EventSource("/__xtask/live-reload");
+    source.addEventListener("reload", () => location.reload());
+    source.addEventListener("stylesheets", () => {
+        document.querySelectorAll('link[rel="stylesheet"]').forEach(link => {
+            const url = new URL(link.href);
+            url.searchParams.set("xtask-live-reload", Date.now());
+            link.href = url.href;
+        });
+    });
+})();
+</script>
+"#;
+
+////////////////////////////////////////////////////////////////////////////////
+// CLI Tasks
+////////////////////////////////////////////////////////////////////////////////
+
+pub fn serve_web_distribution() -> TaskResult {
+    build_web_distribution()?;
+    let web_dist_path = paths::BUILD_PATH.join(paths::WEB_DIST_SUBDIRECTORY);
+    let header_rules = HeaderRules::load_for_distribution(&web_dist_path)?;
+    serve_by_path(&web_dist_path, header_rules, None)
+}
+
+pub fn serve_web_distribution_dev() -> TaskResult {
+    let web_dist_path =
+        paths::BUILD_PATH.join(paths::WEB_DIST_DEV_SUBDIRECTORY);
+    let wasm_pkg_path =
+        paths::BUILD_PATH.join(paths::WASM_PKG_DEV_SUBDIRECTORY);
+    let header_rules = HeaderRules::load_for_distribution(&web_dist_path)?;
+    let live_reload = Arc::new(LiveReload::default());
+
+    let server_live_reload = live_reload.clone();
+    let server_web_dist_path = web_dist_path.clone();
+    thread::spawn(move || {
+        if let Err(e) = serve_by_path(
+            &server_web_dist_path,
+            header_rules,
+            Some(server_live_reload),
+        ) {
+            log::error!("[xtask] Server failed: {}", e);
+        }
+    });
+
+    watch_web_distribution_by_path(
+        &web_dist_path,
+        &wasm_pkg_path,
+        false,
+        |changes| {
+            live_reload
+                .broadcast(ReloadEvent::for_changes(changes, &web_dist_path))
+        },
+    )
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// Live Reload
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ReloadEvent {
+    Stylesheets,
+    Page,
+}
+
+impl ReloadEvent {
+    fn name(&self) -> &'static str {
+        match self {
+            ReloadEvent::Stylesheets => "stylesheets",
+            ReloadEvent::Page => "reload",
+        }
+    }
+
+    /// Stylesheets can be hot-swapped only if every changed file is linked
+    /// by built page (`<link rel="stylesheet">`): inline and included css is
+    /// part of page itself
+    pub fn for_changes(changes: &WatchChanges, web_dist_path: &Path) -> Self {
+        if changes.rust_sources || changes.front_page_files.is_empty() {
+            return ReloadEvent::Page;
+        }
+
+        let linked = match linked_stylesheets(web_dist_path) {
+            Ok(linked) => linked,
+            Err(e) => {
+                log::warn!("[xtask] Failed to find linked stylesheets: {}", e);
+                return ReloadEvent::Page;
+            }
+        };
+        let only_linked_stylesheets =
+            changes.front_page_files.iter().all(|path| {
+                path.extension().is_some_and(|ext| ext == "css")
+                    && linked.contains(&url_path(path))
+            });
+
+        if only_linked_stylesheets {
+            ReloadEvent::Stylesheets
+        } else {
+            ReloadEvent::Page
+        }
+    }
+}
+
+/// Broadcasts reload events to every connected page
+#[derive(Default)]
+pub struct LiveReload {
+    clients: Mutex<Vec<mpsc::Sender<ReloadEvent>>>,
+}
+
+impl LiveReload {
+    pub fn broadcast(&self, event: ReloadEvent) {
+        let mut clients = self.clients.lock().expect("poisoned clients");
+        log::info!(
+            "[xtask] Live reload ({}) of {} client(s)",
+            event.name(),
+            clients.len()
+        );
+        // disconnected clients dropped their receivers
+        clients.retain(|client| client.send(event).is_ok());
+    }
+
+    fn subscribe(&self) -> mpsc::Receiver<ReloadEvent> {
+        let (sender, receiver) = mpsc::channel();
+        self.clients.lock().expect("poisoned clients").push(sender);
+        receiver
+    }
+}
+
+/// Add live reload client script to HTML page (right before `</body>`)
+///
+/// # Examples
+///
+/// ```
+/// use xtask::web_server::{LIVE_RELOAD_ENDPOINT, inject_live_reload};
+///
+/// let page = inject_live_reload("<body><p>hi</p></body>");
+/// assert!(page.starts_with("<body><p>hi</p><script>"));
+/// assert!(page.ends_with("</script>\n</body>"));
+/// assert!(page.contains(LIVE_RELOAD_ENDPOINT));
+///
+/// assert!(inject_live_reload("<p>hi</p>").contains(LIVE_RELOAD_ENDPOINT));
+/// ```
+pub fn inject_live_reload(html: &str) -> String {
+    match html.rfind("</body>") {
+        Some(index) => {
+            let (head, tail) = html.split_at(index);
+            format!("{}{}{}", head, LIVE_RELOAD_SCRIPT, tail)
+        }
+        None => format!("{}{}", html, LIVE_RELOAD_SCRIPT),
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// HTTP Server
+////////////////////////////////////////////////////////////////////////////////
+
+/// Everything connection handlers need to know
+struct Server {
+    root: PathBuf,
+    header_rules: HeaderRules,
+    live_reload: Option<Arc<LiveReload>>,
+}
+
+/// Serve `root` (thread per connection) until listener fails
+pub fn serve_by_path(
+    root: &Path,
+    header_rules: HeaderRules,
+    live_reload: Option<Arc<LiveReload>>,
+) -> TaskResult {
+    let server = Arc::new(Server {
+        root: root.to_owned(),
+        header_rules,
+        live_reload,
+    });
+    let listener = TcpListener::bind(SERVE_ADDRESS)?;
+    log::info!(
+        "[xtask] Serving {} on http://{}",
+        root.display(),
+        SERVE_ADDRESS
+    );
+
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                log::error!("[xtask] Failed to accept connection: {}", e);
+                continue;
+            }
+        };
+        let server = server.clone();
+        thread::spawn(move || {
+            if let Err(e) = handle_connection(stream, &server) {
+                log::debug!("[xtask] Connection closed with error: {}", e);
+            }
+        });
+    }
+
+    OK
+}
+
+fn handle_connection(mut stream: TcpStream, server: &Server) -> TaskResult {
+    let mut reader = BufReader::new(stream.try_clone()?);
+
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+
+    // headers are not used (yet)
+    loop {
+        let mut header_line = String::new();
+        if reader.read_line(&mut header_line)? == 0
+            || header_line.trim_end().is_empty()
+        {
+            break;
+        }
+    }
+
+    let mut request_parts = request_line.split_whitespace();
+    let method = request_parts.next().unwrap_or_default();
+    let target = request_parts.next().unwrap_or_default();
+    log::info!("[xtask] {} {}", method, target);
+
+    let head_only = match method {
+        "GET" => false,
+        "HEAD" => true,
+        _ => {
+            return write_response(
+                &mut stream,
+                "405 Method Not Allowed",
+                &[("Allow", "GET, HEAD")],
+                b"",
+                false,
+            );
+        }
+    };
+
+    if let Some(live_reload) = server.live_reload.as_deref()
+        && target == LIVE_RELOAD_ENDPOINT
+    {
+        return stream_live_reload(&mut stream, live_reload);
+    }
+
+    let (status, headers, body) = serve_file(server, target);
+    write_response(&mut stream, status, &headers, &body, head_only)
+}
+
+/// Status, headers and body of response to `GET target`: live reload client
+/// is added to served HTML pages only here, files of `root` stay untouched
+fn serve_file<'a>(
+    server: &'a Server,
+    target: &str,
+) -> (&'static str, Vec<(&'a str, &'a str)>, Vec<u8>) {
+    let (status, file_path) = match resolve_request_path(&server.root, target) {
+        Some(file_path) => ("200 OK", file_path),
+        None => ("404 Not Found", not_found_page_path(&server.root)),
+    };
+
+    let mut body = fs::read(&file_path).unwrap_or_default();
+    let mut headers = vec![("Content-Type", content_type(&file_path))];
+
+    // like nginx, match rules against path of actually served file
+    if let Ok(relative_path) = file_path.strip_prefix(&server.root) {
+        let url_path = format!("/{}", relative_path.to_string_lossy());
+        for (name, value) in server.header_rules.headers_for(&url_path) {
+            match headers
+                .iter_mut()
+                .find(|(known, _)| known.eq_ignore_ascii_case(name))
+            {
+                Some((_, known_value)) => *known_value = value,
+                None => headers.push((name, value)),
+            }
+        }
+    }
+
+    if server.live_reload.is_some() && headers[0].1.starts_with("text/html") {
+        body = inject_live_reload(&String::from_utf8_lossy(&body)).into_bytes();
+    }
+
+    (status, headers, body)
+}
+
+/// Server-sent events stream, lives until client disconnects
+fn stream_live_reload(
+    stream: &mut TcpStream,
+    live_reload: &LiveReload,
+) -> TaskResult {
+    let events = live_reload.subscribe();
+
+    stream.write_all(
+        b"HTTP/1.1 200 OK\r\n\
+          Content-Type: text/event-stream\r\n\
+          Cache-Control: no-cache\r\n\
+          Connection: keep-alive\r\n\r\n",
+    )?;
+    stream.flush()?;
+
+    loop {
+        let message = match events.recv_timeout(LIVE_RELOAD_PING_INTERVAL) {
+            Ok(event) => format!("event: {}\ndata:\n\n", event.name()),
+            Err(mpsc::RecvTimeoutError::Timeout) => ": ping\n\n".to_owned(),
+            Err(mpsc::RecvTimeoutError::Disconnected) => return OK,
+        };
+        stream.write_all(message.as_bytes())?;
+        stream.flush()?;
+    }
+}
+
+fn write_response(
+    stream: &mut TcpStream,
+    status: &str,
+    headers: &[(&str, &str)],
+    body: &[u8],
+    head_only: bool,
+) -> Result<()> {
+    let mut response = format!(
+        "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n",
+        status,
+        body.len()
+    );
+    for (name, value) in headers {
+        response.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    response.push_str("\r\n");
+
+    stream.write_all(response.as_bytes())?;
+    if !head_only {
+        stream.write_all(body)?;
+    }
+    stream.flush()
+}
+
+/// Map request target onto existing file in `root` (`None` if not found).
+/// Query and fragment are ignored, directories resolve to their index.
+fn resolve_request_path(root: &Path, target: &str) -> Option<PathBuf> {
+    let path = target.split(['?', '#']).next().unwrap_or_default();
+    let path = percent_decode(path)?;
+
+    let relative_path = Path::new(path.trim_start_matches('/'));
+    // never leave root
+    if relative_path
+        .components()
+        .any(|component| !matches!(component, Component::Normal(_)))
+    {
+        return None;
+    }
+
+    let mut file_path = root.join(relative_path);
+    if file_path.is_dir() {
+        file_path.push(INDEX_FILE);
+    }
+
+    file_path.is_file().then_some(file_path)
+}
+
+/// Release distribution hides error pages, developer one doesn't
+fn not_found_page_path(root: &Path) -> PathBuf {
+    [HIDDEN_ERROR_PAGE_DIR, ERROR_PAGE_SUBDIR]
+        .iter()
+        .map(|dir| root.join(dir).join(NOT_FOUND_PAGE))
+        .find(|path| path.is_file())
+        .unwrap_or_default()
+}
+
+fn content_type(path: &Path) -> &'static str {
+    let extension = path
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default();
+
+    match extension.as_str() {
+        "html" => "text/html; charset=utf-8",
+        "css" => "text/css; charset=utf-8",
+        "js" => "text/javascript; charset=utf-8",
+        "json" => "application/json",
+        "webmanifest" => "application/manifest+json",
+        "wasm" => "application/wasm",
+        "ico" => "image/x-icon",
+        "png" => "image/png",
+        "svg" => "image/svg+xml",
+        "otf" => "font/otf",
+        "woff2" => "font/woff2",
+        "txt" => "text/plain; charset=utf-8",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Decode `%XX` sequences (`None` on malformed or non-utf8 input)
+fn percent_decode(input: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(input.len());
+    let mut input_bytes = input.bytes();
+
+    while let Some(byte) = input_bytes.next() {
+        if byte == b'%' {
+            let hex = [input_bytes.next()?, input_bytes.next()?];
+            let hex = std::str::from_utf8(&hex).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+        } else {
+            bytes.push(byte);
+        }
+    }
+
+    String::from_utf8(bytes).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn live_reload_is_added_only_to_responses_of_dev_server() {
+        let root = tempfile::tempdir().expect("temp dir");
+        let page = "<body><p>hi</p></body>";
+        fs::write(root.path().join(INDEX_FILE), page).unwrap();
+        fs::write(root.path().join("style.css"), "p{}").unwrap();
+        let server_with = |live_reload| Server {
+            root: root.path().to_owned(),
+            header_rules: HeaderRules::default(),
+            live_reload,
+        };
+        let body_of = |server: &Server, target| {
+            String::from_utf8(serve_file(server, target).2).unwrap()
+        };
+
+        let release = server_with(None);
+        assert_eq!(body_of(&release, "/"), page);
+
+        let dev = server_with(Some(Arc::new(LiveReload::default())));
+        assert!(body_of(&dev, "/").contains(LIVE_RELOAD_ENDPOINT));
+        assert!(body_of(&dev, "/missing").is_empty());
+        assert_eq!(body_of(&dev, "/style.css"), "p{}");
+
+        // served page is injected, file itself is not
+        assert_eq!(
+            fs::read_to_string(root.path().join(INDEX_FILE)).unwrap(),
+            page
+        );
+    }
+
+    #[test]
+    fn it_resolves_only_files_inside_root() {
+        let root = tempfile::tempdir().expect("temp dir");
+        fs::write(root.path().join(INDEX_FILE), "index").unwrap();
+        fs::create_dir(root.path().join("fonts")).unwrap();
+        fs::write(root.path().join("fonts").join("a b.otf"), "font").unwrap();
+
+        let index = Some(root.path().join(INDEX_FILE));
+        assert_eq!(resolve_request_path(root.path(), "/"), index);
+        assert_eq!(resolve_request_path(root.path(), "/?v=1"), index);
+        assert_eq!(resolve_request_path(root.path(), "/index.html"), index);
+        assert_eq!(
+            resolve_request_path(root.path(), "/fonts/a%20b.otf"),
+            Some(root.path().join("fonts").join("a b.otf"))
+        );
+        assert_eq!(resolve_request_path(root.path(), "/fonts"), None);
+        assert_eq!(resolve_request_path(root.path(), "/missing"), None);
+        assert_eq!(resolve_request_path(root.path(), "/../index.html"), None);
+        assert_eq!(resolve_request_path(root.path(), "/%2e%2e/x"), None);
+        assert_eq!(resolve_request_path(root.path(), "/%zz"), None);
+    }
+
+    #[test]
+    fn only_linked_stylesheet_changes_are_hot_swapped() {
+        let web_dist = tempfile::tempdir().expect("temp dir");
+        fs::write(
+            web_dist.path().join(INDEX_FILE),
+            r#"<link rel="stylesheet" href="/style.css?v=1"><style>a{}</style>"#,
+        )
+        .unwrap();
+        let event_of = |files: &[&str]| {
+            let changes = WatchChanges {
+                front_page_files: files.iter().map(PathBuf::from).collect(),
+                ..Default::default()
+            };
+            ReloadEvent::for_changes(&changes, web_dist.path())
+        };
+
+        assert_eq!(event_of(&["style.css"]), ReloadEvent::Stylesheets);
+        // inlined by `#include`
+        assert_eq!(event_of(&["partials/font.css"]), ReloadEvent::Page);
+        assert_eq!(event_of(&["other.css"]), ReloadEvent::Page);
+        assert_eq!(event_of(&["style.css", INDEX_FILE]), ReloadEvent::Page);
+
+        let changes = WatchChanges {
+            rust_sources: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            ReloadEvent::for_changes(&changes, web_dist.path()),
+            ReloadEvent::Page
+        );
+    }
+}
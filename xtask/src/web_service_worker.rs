@@ -0,0 +1,202 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{Error, Result};
+use std::path::Path;
+
+use sha2::{Digest, Sha256};
+
+use crate::core_dist::{OK, TaskResult, to_hex};
+use crate::web_build_manifest::BuildManifest;
+use crate::web_dist::front_page_path;
+use crate::web_fingerprint::FINGERPRINT_MANIFEST_FILE;
+use crate::web_template::Template;
+use crate::web_wasm_loader::WASM_LOADER_FILE;
+
+////////////////////////////////////////////////////////////////////////////////
+// Service Worker Settings
+////////////////////////////////////////////////////////////////////////////////
+
+/// Written into distribution root (scope of worker is whole site)
+pub const SERVICE_WORKER_FILE: &str = "service-worker.js";
+
+/// Source of worker (relative to front-page), expanded with `cache_version`,
+/// `precache` and `immutable` variables
+const SERVICE_WORKER_TEMPLATE: &str = "partials/service-worker.js";
+
+/// Files downloaded on worker install (besides wasm loader): wasm and glue
+/// modules are cached once page loads them, so installing worker never
+/// downloads variants page does not pick
+const PRECACHE_EXTENSIONS: &[&str] = &["html", "css", "otf", "woff2"];
+
+/// Directory pages are requested by directory URL
+const INDEX_PAGE: &str = "index.html";
+
+const CACHE_VERSION_LENGTH: usize = 16;
+
+////////////////////////////////////////////////////////////////////////////////
+// Service Worker
+////////////////////////////////////////////////////////////////////////////////
+
+/// Write service worker precaching pages, styles, fonts and wasm loader of
+/// release distribution in `web_dist_path` (listed by its build manifest, so
+/// runs after fingerprinting and securing pages). Cache version is derived
+/// from precached content.
+pub fn generate_service_worker(web_dist_path: &Path) -> TaskResult {
+    let build_manifest = BuildManifest::collect(web_dist_path, true)?;
+    let fingerprinted: BTreeMap<String, String> = serde_json::from_slice(
+        &fs::read(web_dist_path.join(FINGERPRINT_MANIFEST_FILE))?,
+    )
+    .map_err(Error::other)?;
+
+    let loader = fingerprinted
+        .get(WASM_LOADER_FILE)
+        .map(String::as_str)
+        .unwrap_or(WASM_LOADER_FILE);
+
+    let mut precache = vec![];
+    let mut hasher = Sha256::new();
+    for (path, output) in build_manifest.outputs.iter() {
+        let url = if path == loader {
+            Some(format!("/{}", path))
+        } else {
+            precache_url(path)
+        };
+        if let Some(url) = url {
+            hasher.update(format!("{} {}\n", url, output.sha256));
+            precache.push(url);
+        }
+    }
+    let cache_version = format!(
+        "{}-{}",
+        build_manifest.commit,
+        &to_hex(&hasher.finalize())[..CACHE_VERSION_LENGTH]
+    );
+    let immutable: Vec<_> = fingerprinted
+        .values()
+        .map(|path| format!("/{}", path))
+        .collect();
+
+    let front_page_path = front_page_path();
+    let template = Template {
+        variables: [
+            ("cache_version", cache_version.clone()),
+            ("precache", to_json(&precache)?),
+            ("immutable", to_json(&immutable)?),
+        ]
+        .into_iter()
+        .map(|(name, value)| (name.to_owned(), value))
+        .collect(),
+        root: front_page_path.clone(),
+        line_comments: false,
+    };
+    let worker = template.expand(
+        &fs::read_to_string(front_page_path.join(SERVICE_WORKER_TEMPLATE))?,
+        SERVICE_WORKER_TEMPLATE,
+    )?;
+
+    let worker_path = web_dist_path.join(SERVICE_WORKER_FILE);
+    log::info!(
+        "[xtask] Writing {} ({} precached files, version {})",
+        worker_path.display(),
+        precache.len(),
+        cache_version
+    );
+    fs::write(worker_path, worker)?;
+
+    OK
+}
+
+/// URL of distribution page, style or font (path relative to root) if it is
+/// precached: hidden files (error pages) are not.
+///
+/// # Examples
+///
+/// ```
+/// use xtask::web_service_worker::precache_url;
+///
+/// assert_eq!(precache_url("index.html").as_deref(), Some("/"));
+/// assert_eq!(precache_url("a/index.html").as_deref(), Some("/a/"));
+/// assert_eq!(
+///     precache_url("fonts/a.0123456789abcdef.woff2").as_deref(),
+///     Some("/fonts/a.0123456789abcdef.woff2")
+/// );
+/// assert_eq!(precache_url("shards_browser_bg.0123456789abcdef.wasm"), None);
+/// assert_eq!(precache_url("manifest.json"), None);
+/// assert_eq!(precache_url("index.html.br"), None);
+/// assert_eq!(precache_url(".error_pages/404.html"), None);
+/// ```
+pub fn precache_url(path: &str) -> Option<String> {
+    let extension = Path::new(path).extension()?.to_str()?;
+    if !PRECACHE_EXTENSIONS.contains(&extension)
+        || path.split('/').any(|segment| segment.starts_with('.'))
+    {
+        return None;
+    }
+
+    let directory = path.strip_suffix(INDEX_PAGE);
+    match directory {
+        Some(directory) if directory.is_empty() || directory.ends_with('/') => {
+            Some(format!("/{}", directory))
+        }
+        _ => Some(format!("/{}", path)),
+    }
+}
+
+fn to_json(urls: &[String]) -> Result<String> {
+    serde_json::to_string(urls).map_err(Error::other)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn worker_precaches_pages_and_loader_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let web_dist = dir.path();
+        fs::create_dir_all(web_dist.join(".error_pages")).unwrap();
+        fs::write(web_dist.join("index.html"), "<html></html>").unwrap();
+        fs::write(web_dist.join(".error_pages/404.html"), "404").unwrap();
+        fs::write(web_dist.join("a.0123456789abcdef.wasm"), "wasm").unwrap();
+        fs::write(web_dist.join("favicon.ico"), "ico").unwrap();
+        fs::write(web_dist.join("shards_browser_loader.0.js"), "js").unwrap();
+        fs::write(
+            web_dist.join(FINGERPRINT_MANIFEST_FILE),
+            r#"{
+                "a.wasm": "a.0123456789abcdef.wasm",
+                "shards_browser_loader.js": "shards_browser_loader.0.js"
+            }"#,
+        )
+        .unwrap();
+
+        generate_service_worker(web_dist).unwrap();
+        let worker =
+            fs::read_to_string(web_dist.join(SERVICE_WORKER_FILE)).unwrap();
+        // wasm is cached once page loads it (cache first as immutable)
+        assert!(
+            worker.contains(
+                r#"const PRECACHE = ["/","/shards_browser_loader.0.js"];"#
+            ),
+            "{}",
+            worker
+        );
+        assert!(worker.contains(
+            r#"new Set(["/a.0123456789abcdef.wasm","/shards_browser_loader.0.js"])"#
+        ));
+        assert!(!worker.contains("#echo"));
+
+        // any precached change makes new version (and drops old cache)
+        let version = |worker: &str| {
+            worker
+                .lines()
+                .find(|line| line.contains("CACHE_NAME ="))
+                .map(|line| line.to_owned())
+        };
+        let before = version(&worker);
+        fs::write(web_dist.join("index.html"), "<html>2</html>").unwrap();
+        generate_service_worker(web_dist).unwrap();
+        let worker =
+            fs::read_to_string(web_dist.join(SERVICE_WORKER_FILE)).unwrap();
+        assert_ne!(version(&worker), before);
+    }
+}
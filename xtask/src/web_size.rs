@@ -0,0 +1,536 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{Error, ErrorKind, Result};
+use std::path::Path;
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::core_dist::{
+    DistributionPath, FailurePolicy, OK, TaskResult, url_path,
+};
+use crate::paths;
+use crate::web_build_manifest::BUILD_MANIFEST_FILE;
+use crate::web_compress::Encoding;
+use crate::web_fingerprint::FINGERPRINT_MANIFEST_FILE;
+use crate::web_headers::glob_to_regex;
+
+////////////////////////////////////////////////////////////////////////////////
+// Size Report Settings
+////////////////////////////////////////////////////////////////////////////////
+
+/// Report of web distribution is stored next to it as `<web-dist>.<ext>`
+const SIZE_REPORT_EXTENSION: &str = "size-report.json";
+
+/// Report of build before last one (baseline of diff)
+const PREVIOUS_SIZE_REPORT_EXTENSION: &str = "size-report.previous.json";
+
+/// Pre-compressed siblings are measured as part of original file
+const SKIPPED_EXTENSIONS: &[&[u8]] = &[b"gz", b"br"];
+
+////////////////////////////////////////////////////////////////////////////////
+// CLI Tasks
+////////////////////////////////////////////////////////////////////////////////
+
+/// Measure release distribution, print diff against report of previous build
+/// and fail if any size budget is exceeded
+pub fn size_report() -> TaskResult {
+    let web_dist_path = paths::BUILD_PATH.join(paths::WEB_DIST_SUBDIRECTORY);
+    if !web_dist_path.is_dir() {
+        return Err(Error::new(
+            ErrorKind::NotFound,
+            "no web distribution, run `cargo xtask build-web-dist` first",
+        ));
+    }
+    let report = SizeReport::measure(&web_dist_path)?;
+    check_size_report(&web_dist_path, &report)
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// Size Report
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct FileSizes {
+    pub raw: usize,
+    pub gzip: usize,
+    pub brotli: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Measure {
+    Raw,
+    Gzip,
+    Brotli,
+}
+
+impl Measure {
+    pub fn of(&self, sizes: &FileSizes) -> usize {
+        match self {
+            Measure::Raw => sizes.raw,
+            Measure::Gzip => sizes.gzip,
+            Measure::Brotli => sizes.brotli,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Measure::Raw => "raw",
+            Measure::Gzip => "gzip",
+            Measure::Brotli => "brotli",
+        }
+    }
+}
+
+/// Sizes of every distribution file by original (not fingerprinted) URL
+/// path, so reports of different builds are comparable (originals kept
+/// next to their fingerprinted copies are measured once, as the latter)
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SizeReport {
+    pub files: BTreeMap<String, FileSizes>,
+}
+
+impl SizeReport {
+    pub fn measure(web_dist_path: &Path) -> Result<Self> {
+        let original_names = original_names(web_dist_path)?;
+        let mut files = vec![];
+        web_dist_path.for_each_file_recursively(
+            FailurePolicy::FailFast,
+            |relative_path| {
+                if !relative_path.contains_any_extension(SKIPPED_EXTENSIONS)
+                    && relative_path != Path::new(BUILD_MANIFEST_FILE)
+                {
+                    files.push(relative_path.to_owned());
+                }
+                OK
+            },
+        )?;
+
+        let mut report = SizeReport::default();
+        for file in files {
+            let name = url_path(&file);
+            let name = match original_names.get(&name) {
+                Some(original) => original.clone(),
+                // kept original (e.g. `favicon.ico`) of fingerprinted file
+                None if original_names.values().any(|o| *o == name) => {
+                    continue;
+                }
+                None => name,
+            };
+            let sizes = measure_file(&web_dist_path.join(&file))?;
+            report.files.insert(name, sizes);
+        }
+
+        Ok(report)
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        serde_json::from_slice(&fs::read(path)?).map_err(Error::other)
+    }
+
+    pub fn save(&self, path: &Path) -> TaskResult {
+        fs::write(
+            path,
+            serde_json::to_string_pretty(self).map_err(Error::other)?,
+        )
+    }
+
+    fn total(&self) -> FileSizes {
+        self.files
+            .values()
+            .fold(FileSizes::default(), |total, sizes| FileSizes {
+                raw: total.raw + sizes.raw,
+                gzip: total.gzip + sizes.gzip,
+                brotli: total.brotli + sizes.brotli,
+            })
+    }
+}
+
+/// Store report of just built distribution next to it (report of previous
+/// build becomes baseline), then compare it with budgets and baseline
+pub fn check_size_budgets(web_dist_path: &Path) -> TaskResult {
+    let report_path = web_dist_path.with_extension(SIZE_REPORT_EXTENSION);
+    if report_path.is_file() {
+        fs::rename(
+            &report_path,
+            web_dist_path.with_extension(PREVIOUS_SIZE_REPORT_EXTENSION),
+        )?;
+    }
+
+    let report = SizeReport::measure(web_dist_path)?;
+    log::info!("[xtask] Writing {}", report_path.display());
+    report.save(&report_path)?;
+
+    check_size_report(web_dist_path, &report)
+}
+
+/// Compare report of distribution with budgets and report of previous build
+fn check_size_report(web_dist_path: &Path, report: &SizeReport) -> TaskResult {
+    let budgets = SizeBudgets::load(&paths::SIZE_BUDGETS_PATH)?;
+    let previous_path =
+        web_dist_path.with_extension(PREVIOUS_SIZE_REPORT_EXTENSION);
+    let previous = match SizeReport::load(&previous_path) {
+        Ok(previous) => Some(previous),
+        Err(e) => {
+            log::debug!("[xtask] No previous size report: {}", e);
+            None
+        }
+    };
+
+    let violations = budgets.violations(report);
+    log_report(report, previous.as_ref(), &violations);
+
+    if violations.is_empty() {
+        log::info!("[xtask] All size budgets are met");
+        return OK;
+    }
+    for violation in violations.iter() {
+        log::error!(
+            "[xtask] {} is {} B {} (budget {} B by `{}`)",
+            violation.file,
+            violation.size,
+            violation.measure.name(),
+            violation.budget,
+            violation.glob
+        );
+    }
+    Err(Error::other(format!(
+        "{} size budget(s) exceeded, see {}",
+        violations.len(),
+        paths::SIZE_BUDGETS_PATH.display()
+    )))
+}
+
+fn measure_file(path: &Path) -> Result<FileSizes> {
+    let data = fs::read(path)?;
+    let mut compressed_sizes = [0; 2];
+
+    for (encoding, size) in
+        Encoding::ALL.iter().zip(compressed_sizes.iter_mut())
+    {
+        // reuse pre-compressed sibling (same encoder settings) if any
+        let mut sibling = path.to_owned().into_os_string();
+        sibling.push(".");
+        sibling.push(encoding.extension());
+
+        *size = match fs::metadata(&sibling) {
+            Ok(metadata) => metadata.len() as usize,
+            Err(_) => encoding.compress(&data)?.len(),
+        };
+    }
+
+    Ok(FileSizes {
+        raw: data.len(),
+        gzip: compressed_sizes[0],
+        brotli: compressed_sizes[1],
+    })
+}
+
+/// Fingerprinted URL path -> original one (from fingerprint manifest)
+fn original_names(web_dist_path: &Path) -> Result<BTreeMap<String, String>> {
+    let manifest_path = web_dist_path.join(FINGERPRINT_MANIFEST_FILE);
+    if !manifest_path.is_file() {
+        return Ok(BTreeMap::new());
+    }
+
+    let manifest: BTreeMap<String, String> =
+        serde_json::from_slice(&fs::read(manifest_path)?)
+            .map_err(Error::other)?;
+    Ok(manifest
+        .into_iter()
+        .map(|(original, fingerprinted)| (fingerprinted, original))
+        .collect())
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// Size Budgets
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug)]
+pub struct SizeBudget {
+    pub glob: String,
+    pub measure: Measure,
+    pub max_size: usize,
+    regex: Regex,
+}
+
+/// Budgets from `deploy/size-budgets.conf` (see it for format)
+#[derive(Debug, Default)]
+pub struct SizeBudgets {
+    pub budgets: Vec<SizeBudget>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct BudgetViolation {
+    pub file: String,
+    pub glob: String,
+    pub measure: Measure,
+    pub size: usize,
+    pub budget: usize,
+}
+
+impl SizeBudgets {
+    pub fn load(path: &Path) -> Result<Self> {
+        log::debug!("[xtask] Loading size budgets {}", path.display());
+        let content = fs::read_to_string(path)?;
+        Self::parse(&content).map_err(|e| {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!("{}:{}", path.display(), e),
+            )
+        })
+    }
+
+    /// Parse size budgets (errors are prefixed with line number)
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xtask::web_size::{Measure, SizeBudgets};
+    ///
+    /// let budgets = SizeBudgets::parse(
+    ///     "# comment\n/**/*.wasm brotli 1.5 MiB\n/** raw 100B\n",
+    /// )
+    /// .unwrap();
+    /// assert_eq!(budgets.budgets[0].measure, Measure::Brotli);
+    /// assert_eq!(budgets.budgets[0].max_size, 1572864);
+    /// assert_eq!(budgets.budgets[1].max_size, 100);
+    ///
+    /// assert!(SizeBudgets::parse("/** zstd 1 KiB").is_err());
+    /// assert!(SizeBudgets::parse("/** raw 1 KB").is_err());
+    /// assert!(SizeBudgets::parse("*.wasm raw 1 KiB").is_err());
+    /// ```
+    pub fn parse(content: &str) -> Result<Self> {
+        let mut budgets = vec![];
+
+        for (index, line) in content.lines().enumerate() {
+            let line_error = |message: &str| {
+                Error::new(
+                    ErrorKind::InvalidData,
+                    format!("{}: {}", index + 1, message),
+                )
+            };
+            let trimmed = line.trim();
+
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+
+            let mut fields = trimmed.split_whitespace();
+            let (Some(glob), Some(measure)) = (fields.next(), fields.next())
+            else {
+                return Err(line_error("expected `<glob> <measure> <size>`"));
+            };
+            if !glob.starts_with('/') {
+                return Err(line_error("glob should start with '/'"));
+            }
+            let measure = match measure {
+                "raw" => Measure::Raw,
+                "gzip" => Measure::Gzip,
+                "brotli" => Measure::Brotli,
+                _ => {
+                    return Err(line_error(
+                        "measure should be raw/gzip/brotli",
+                    ));
+                }
+            };
+            let max_size = parse_size(&fields.collect::<String>())
+                .ok_or_else(|| line_error("size should be like `1.5 MiB`"))?;
+            let regex = Regex::new(&glob_to_regex(glob))
+                .map_err(|e| line_error(&e.to_string()))?;
+
+            budgets.push(SizeBudget {
+                glob: glob.to_owned(),
+                measure,
+                max_size,
+                regex,
+            });
+        }
+
+        Ok(SizeBudgets { budgets })
+    }
+
+    /// Exceeded budgets of every file in report
+    pub fn violations(&self, report: &SizeReport) -> Vec<BudgetViolation> {
+        let mut violations = vec![];
+
+        for (file, sizes) in report.files.iter() {
+            let url_path = format!("/{}", file);
+            for budget in self.budgets.iter() {
+                let size = budget.measure.of(sizes);
+                if budget.regex.is_match(&url_path) && size > budget.max_size {
+                    violations.push(BudgetViolation {
+                        file: file.clone(),
+                        glob: budget.glob.clone(),
+                        measure: budget.measure,
+                        size,
+                        budget: budget.max_size,
+                    });
+                }
+            }
+        }
+
+        violations
+    }
+}
+
+/// `1.5 MiB` -> bytes (`B`, `KiB`, `MiB` units)
+fn parse_size(text: &str) -> Option<usize> {
+    let unit_start = text.find(|c: char| c.is_ascii_alphabetic())?;
+    let (number, unit) = text.split_at(unit_start);
+    let multiplier = match unit {
+        "B" => 1.0,
+        "KiB" => 1024.0,
+        "MiB" => 1024.0 * 1024.0,
+        _ => return None,
+    };
+    let number: f64 = number.trim().parse().ok()?;
+    (number >= 0.0).then_some((number * multiplier) as usize)
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// Logging
+////////////////////////////////////////////////////////////////////////////////
+
+fn log_report(
+    report: &SizeReport,
+    previous: Option<&SizeReport>,
+    violations: &[BudgetViolation],
+) {
+    let name_width = report
+        .files
+        .keys()
+        .chain(previous.iter().flat_map(|previous| previous.files.keys()))
+        .map(|name| name.len())
+        .max()
+        .unwrap_or_default()
+        .max("total".len());
+    let has_previous = previous.is_some();
+    let delta_cell = |size: usize, before: Option<usize>| match before {
+        Some(before) if before == size => String::new(),
+        Some(before) => format!("{:+}", size as i64 - before as i64),
+        None if has_previous => "new".to_owned(),
+        None => String::new(),
+    };
+    let row = |name: &str, sizes: &FileSizes, before: Option<&FileSizes>| {
+        format!(
+            "{:name_width$} {:>10} {:>10} {:>10} {:>10} {:>10}",
+            name,
+            sizes.raw,
+            sizes.gzip,
+            sizes.brotli,
+            delta_cell(sizes.raw, before.map(|before| before.raw)),
+            delta_cell(sizes.brotli, before.map(|before| before.brotli)),
+        )
+        .trim_end()
+        .to_owned()
+    };
+
+    log::info!("[xtask] Size report:");
+    log::info!(
+        "[xtask] {:name_width$} {:>10} {:>10} {:>10} {:>10} {:>10}",
+        "file",
+        "raw",
+        "gzip",
+        "brotli",
+        "raw diff",
+        "br diff"
+    );
+    for (name, sizes) in report.files.iter() {
+        let before = previous.and_then(|previous| previous.files.get(name));
+        let mark = if violations.iter().any(|v| &v.file == name) {
+            " <- over budget"
+        } else {
+            ""
+        };
+        log::info!("[xtask] {}{}", row(name, sizes, before), mark);
+    }
+
+    if let Some(previous) = previous {
+        for (name, sizes) in previous.files.iter() {
+            if !report.files.contains_key(name) {
+                log::info!(
+                    "[xtask] {:name_width$} removed (was {} raw, {} brotli)",
+                    name,
+                    sizes.raw,
+                    sizes.brotli
+                );
+            }
+        }
+    }
+
+    let previous_total = previous.map(SizeReport::total);
+    log::info!(
+        "[xtask] {}",
+        row("total", &report.total(), previous_total.as_ref())
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checked_in_budgets_catch_oversized_wasm() {
+        let budgets = SizeBudgets::load(&paths::SIZE_BUDGETS_PATH)
+            .expect("valid deploy/size-budgets.conf");
+
+        let mut report = SizeReport::default();
+        let wasm = FileSizes {
+            raw: 4 << 20,
+            gzip: 3 << 20,
+            brotli: 2 << 20,
+        };
+        report
+            .files
+            .insert("shards_browser_bg.wasm".to_owned(), wasm);
+
+        let violations = budgets.violations(&report);
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].measure, Measure::Brotli);
+    }
+
+    #[test]
+    fn kept_originals_are_measured_as_fingerprinted_copies() {
+        let web_dist = tempfile::tempdir().expect("temp dir");
+        fs::write(web_dist.path().join("favicon.ico"), "ico").unwrap();
+        fs::write(web_dist.path().join("favicon.0123abcd.ico"), "icon")
+            .unwrap();
+        fs::write(
+            web_dist.path().join(FINGERPRINT_MANIFEST_FILE),
+            r#"{"favicon.ico":"favicon.0123abcd.ico"}"#,
+        )
+        .unwrap();
+
+        let report = SizeReport::measure(web_dist.path()).unwrap();
+        assert_eq!(
+            report.files.keys().collect::<Vec<_>>(),
+            ["favicon.ico", FINGERPRINT_MANIFEST_FILE]
+        );
+        assert_eq!(report.files["favicon.ico"].raw, 4);
+    }
+
+    #[test]
+    fn report_of_previous_build_is_kept_as_baseline() {
+        let build = tempfile::tempdir().expect("temp dir");
+        let web_dist_path = build.path().join("web-dist");
+        fs::create_dir(&web_dist_path).unwrap();
+        let previous_path =
+            web_dist_path.with_extension(PREVIOUS_SIZE_REPORT_EXTENSION);
+
+        fs::write(web_dist_path.join("index.html"), "first").unwrap();
+        check_size_budgets(&web_dist_path).unwrap();
+        assert!(!previous_path.exists());
+
+        fs::write(web_dist_path.join("index.html"), "second!").unwrap();
+        check_size_budgets(&web_dist_path).unwrap();
+        let previous = SizeReport::load(&previous_path).unwrap();
+        assert_eq!(previous.files["index.html"].raw, 5);
+
+        // standalone report does not replace baseline
+        let report = SizeReport::measure(&web_dist_path).unwrap();
+        check_size_report(&web_dist_path, &report).unwrap();
+        let previous = SizeReport::load(&previous_path).unwrap();
+        assert_eq!(previous.files["index.html"].raw, 5);
+    }
+}
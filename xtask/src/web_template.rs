@@ -0,0 +1,268 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{Error, ErrorKind, Result};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::html::line_number;
+use crate::paths;
+use crate::web_app::WebApp;
+use crate::web_service_worker::SERVICE_WORKER_FILE;
+
+////////////////////////////////////////////////////////////////////////////////
+// Template Settings
+////////////////////////////////////////////////////////////////////////////////
+
+/// Partials directory of front-page (never copied as is)
+pub(crate) const PARTIALS_SUBDIR: &str = "partials";
+
+const DIRECTIVE_START: &str = "<!-- #";
+const DIRECTIVE_END: &str = "-->";
+
+/// Includes of includes (guards against cycles as well)
+const MAX_INCLUDE_DEPTH: usize = 8;
+
+const SHARDS_BROWSER_MANIFEST: &str = "shards-browser/Cargo.toml";
+const UNKNOWN_VALUE: &str = "unknown";
+
+////////////////////////////////////////////////////////////////////////////////
+// Expansion
+////////////////////////////////////////////////////////////////////////////////
+
+/// Expands directives of front-page sources:
+/// `<!-- #include "partials/font.css" -->` (path relative to front-page) and
+/// `<!-- #echo "version" -->` (build variable).
+#[derive(Debug)]
+pub struct Template {
+    pub root: PathBuf,
+    pub variables: BTreeMap<String, String>,
+    /// wrap included text with comments naming partial (developer builds)
+    pub line_comments: bool,
+}
+
+impl Template {
+    /// Template of front-page with build variables: `version` (of
+    /// shards-browser), `commit` (short hash), `profile` (`release` or
+    /// `dev`), `app_head` (theme color, icon and web app manifest tags) and
+    /// `service_worker` (its URL, empty for developer builds)
+    pub fn for_front_page(root: &Path, release: bool) -> Result<Self> {
+        let variables = [
+            ("version", shards_browser_version()?),
+            ("commit", commit_hash()),
+            (
+                "profile",
+                if release { "release" } else { "dev" }.to_owned(),
+            ),
+            ("app_head", WebApp::load()?.head_tags()),
+            (
+                "service_worker",
+                if release {
+                    format!("/{}", SERVICE_WORKER_FILE)
+                } else {
+                    String::new()
+                },
+            ),
+        ]
+        .into_iter()
+        .map(|(name, value)| (name.to_owned(), value))
+        .collect();
+
+        Ok(Template {
+            root: root.to_owned(),
+            variables,
+            line_comments: !release,
+        })
+    }
+
+    /// Expand directives of `text` (`source` names it in errors)
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xtask::web_template::Template;
+    ///
+    /// let root = std::env::temp_dir().join("xtask-doctest-template");
+    /// std::fs::create_dir_all(root.join("partials")).unwrap();
+    /// std::fs::write(root.join("partials/a.css"), "a {}\n").unwrap();
+    ///
+    /// let template = Template {
+    ///     root,
+    ///     variables: [("version".to_owned(), "0.1.0".to_owned())].into(),
+    ///     line_comments: false,
+    /// };
+    ///
+    /// assert_eq!(
+    ///     template
+    ///         .expand(
+    ///             "<!-- #include \"partials/a.css\" -->v<!-- #echo \"version\" -->",
+    ///             "index.html"
+    ///         )
+    ///         .unwrap(),
+    ///     "a {}\nv0.1.0"
+    /// );
+    /// assert!(template.expand("<!-- #echo \"date\" -->", "a.html").is_err());
+    /// ```
+    pub fn expand(&self, text: &str, source: &str) -> Result<String> {
+        self.expand_nested(text, source, 0, true)
+    }
+
+    /// Expand only `#echo` directives of `text`, `#include` ones are kept as
+    /// is (lines of result match lines of source)
+    pub fn expand_variables(&self, text: &str, source: &str) -> Result<String> {
+        self.expand_nested(text, source, 0, false)
+    }
+
+    fn expand_nested(
+        &self,
+        text: &str,
+        source: &str,
+        depth: usize,
+        includes: bool,
+    ) -> Result<String> {
+        let mut expanded = String::with_capacity(text.len());
+        let mut rest = text;
+
+        while let Some(start) = rest.find(DIRECTIVE_START) {
+            let offset = text.len() - rest.len() + start;
+            let error = |message: String| {
+                Error::new(
+                    ErrorKind::InvalidData,
+                    format!(
+                        "{}:{}: {}",
+                        source,
+                        line_number(text, offset),
+                        message
+                    ),
+                )
+            };
+
+            expanded.push_str(&rest[..start]);
+            let directive = &rest[start + DIRECTIVE_START.len()..];
+            let Some(end) = directive.find(DIRECTIVE_END) else {
+                return Err(error("unterminated directive".to_owned()));
+            };
+            let directive_text = &rest[start
+                ..start + DIRECTIVE_START.len() + end + DIRECTIVE_END.len()];
+            rest = &directive[end + DIRECTIVE_END.len()..];
+
+            let directive = directive[..end].trim();
+            let (name, argument) = directive
+                .split_once(char::is_whitespace)
+                .map(|(name, argument)| (name, argument.trim()))
+                .and_then(|(name, argument)| {
+                    argument
+                        .strip_prefix('"')
+                        .and_then(|argument| argument.strip_suffix('"'))
+                        .map(|argument| (name, argument))
+                })
+                .ok_or_else(|| {
+                    error(format!("malformed directive `{}`", directive))
+                })?;
+
+            match name {
+                "include" if !includes => expanded.push_str(directive_text),
+                "include" => {
+                    if depth >= MAX_INCLUDE_DEPTH {
+                        return Err(error(format!(
+                            "includes are nested too deep ({})",
+                            argument
+                        )));
+                    }
+                    let partial = fs::read_to_string(self.root.join(argument))
+                        .map_err(|e| error(format!("{}: {}", argument, e)))?;
+                    let partial = self.expand_nested(
+                        &partial,
+                        argument,
+                        depth + 1,
+                        true,
+                    )?;
+
+                    if self.line_comments {
+                        let (open, close) = comment_delimiters(argument);
+                        expanded.push_str(&format!(
+                            "{open} #include \"{argument}\" {close}\n"
+                        ));
+                        expanded.push_str(&partial);
+                        expanded.push_str(&format!(
+                            "{open} end of \"{argument}\" {close}"
+                        ));
+                    } else {
+                        expanded.push_str(&partial);
+                    }
+                }
+                "echo" => {
+                    let value =
+                        self.variables.get(argument).ok_or_else(|| {
+                            error(format!("unknown variable `{}`", argument))
+                        })?;
+                    expanded.push_str(value);
+                }
+                _ => {
+                    return Err(error(format!("unknown directive `{}`", name)));
+                }
+            }
+        }
+
+        expanded.push_str(rest);
+        Ok(expanded)
+    }
+}
+
+/// Comment syntax of partial (by extension)
+fn comment_delimiters(partial: &str) -> (&'static str, &'static str) {
+    if partial.ends_with(".css") || partial.ends_with(".js") {
+        ("/*", "*/")
+    } else {
+        ("<!--", "-->")
+    }
+}
+
+pub(crate) fn shards_browser_version() -> Result<String> {
+    let manifest_path = paths::PROJECT_ROOT.join(SHARDS_BROWSER_MANIFEST);
+    let manifest: toml::Table = fs::read_to_string(&manifest_path)?
+        .parse()
+        .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+
+    manifest
+        .get("package")
+        .and_then(|package| package.get("version"))
+        .and_then(|version| version.as_str())
+        .map(str::to_owned)
+        .ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!("{} has no package.version", manifest_path.display()),
+            )
+        })
+}
+
+/// Short hash of HEAD (`unknown` outside of git checkout)
+pub(crate) fn commit_hash() -> String {
+    Command::new("git")
+        .args(["rev-parse", "--short", "HEAD"])
+        .current_dir(*paths::PROJECT_ROOT)
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_owned())
+        .unwrap_or_else(|| UNKNOWN_VALUE.to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checked_in_front_page_expands() {
+        let root = paths::PROJECT_ROOT.join("front-page");
+        let template = Template::for_front_page(&root, false).unwrap();
+
+        for source in ["index.html", "error_pages/template.html"] {
+            let text = fs::read_to_string(root.join(source)).unwrap();
+            let expanded = template.expand(&text, source).unwrap();
+
+            assert!(!expanded.contains(DIRECTIVE_START), "{}", source);
+            assert!(expanded.contains("#include \"partials/font.css\""));
+        }
+    }
+}
@@ -0,0 +1,417 @@
+use std::fs;
+use std::io::{Error, ErrorKind, Result};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::core_dist::{
+    OK, TaskResult, cargo, cargo_with_env, make_each_directory,
+    shell_log_piped, task_flag,
+};
+use crate::paths;
+use crate::web_wasm_opt::WasmProfile;
+
+////////////////////////////////////////////////////////////////////////////////
+// Shell Commands
+////////////////////////////////////////////////////////////////////////////////
+
+const RUSTUP: &str = "rustup";
+/// rustup proxy (`CARGO` of xtask is bound to its own toolchain)
+const CARGO_PROXY: &str = "cargo";
+const WASM_BINDGEN: &str = "wasm-bindgen";
+const WASM_BINDGEN_CLI: &str = "wasm-bindgen-cli";
+
+////////////////////////////////////////////////////////////////////////////////
+// Wasm Build Settings
+////////////////////////////////////////////////////////////////////////////////
+
+const WASM_TARGET: &str = "wasm32-unknown-unknown";
+
+const SHARDS_BROWSER_PACKAGE: &str = "shards-browser";
+/// Name of cargo artifact and of generated js-bindings (`<name>.js`,
+/// `<name>_bg.wasm`)
+const SHARDS_BROWSER_LIB: &str = "shards_browser";
+
+const LOCK_FILE: &str = "Cargo.lock";
+
+/// Task flag (of any task building wasm package) adding opt-in variants
+const THREADS_FLAG: &str = "--threads";
+
+/// Build of shards-browser for set of wasm features, front-page loader picks
+/// best supported one (keep in sync with `WASM_VARIANTS` in index.html)
+#[derive(Debug)]
+pub struct WasmVariant {
+    /// appended to package file names (`shards_browser<suffix>.js`)
+    pub suffix: &'static str,
+    pub rustflags: &'static str,
+    /// features allowed for wasm-opt (it may emit them on its own)
+    pub wasm_opt_features: &'static [&'static str],
+    /// rustup toolchain other than one of xtask (with `rust-src` component)
+    pub toolchain: Option<&'static str>,
+    /// additional `cargo build` args
+    pub cargo_args: &'static [&'static str],
+    /// built only with `--threads`
+    pub opt_in: bool,
+}
+
+/// Baseline variant first (the only one in developer builds, besides opt-in
+/// ones)
+pub const WASM_VARIANTS: &[WasmVariant] = &[
+    WasmVariant {
+        suffix: "",
+        rustflags: "-Ctarget-cpu=mvp",
+        wasm_opt_features: &["--enable-mutable-globals"],
+        toolchain: None,
+        cargo_args: &[],
+        opt_in: false,
+    },
+    WasmVariant {
+        suffix: "_simd",
+        rustflags: "-Ctarget-cpu=mvp -Ctarget-feature=+simd128,+bulk-memory",
+        wasm_opt_features: &[
+            "--enable-mutable-globals",
+            "--enable-simd",
+            "--enable-bulk-memory",
+        ],
+        toolchain: None,
+        cargo_args: &[],
+        opt_in: false,
+    },
+    // Shared memory (rustc adds `--shared-memory`, `--import-memory` and TLS
+    // exports to linker args on its own with atomics), std is rebuilt with
+    // atomics, so nightly is required. Page should be cross-origin isolated
+    // (COOP/COEP headers of deploy/headers.conf).
+    WasmVariant {
+        suffix: "_threads",
+        rustflags: "-Ctarget-cpu=mvp \
+                    -Ctarget-feature=+atomics,+bulk-memory,+mutable-globals",
+        wasm_opt_features: &[
+            "--enable-mutable-globals",
+            "--enable-threads",
+            "--enable-bulk-memory",
+        ],
+        toolchain: Some("nightly"),
+        cargo_args: &["-Zbuild-std=panic_abort,std"],
+        opt_in: true,
+    },
+];
+
+/// Variants to build: every non opt-in one for release (baseline only for
+/// developer builds) and opt-in ones if requested by task flags
+pub fn selected_wasm_variants(release: bool) -> Vec<&'static WasmVariant> {
+    let threads = task_flag(THREADS_FLAG);
+    WASM_VARIANTS
+        .iter()
+        .filter(|variant| {
+            if variant.opt_in {
+                threads
+            } else {
+                release || variant.is_baseline()
+            }
+        })
+        .collect()
+}
+
+impl WasmVariant {
+    pub fn is_baseline(&self) -> bool {
+        self.suffix.is_empty()
+    }
+
+    /// `shards_browser<suffix>`
+    pub fn lib_name(&self) -> String {
+        format!("{}{}", SHARDS_BROWSER_LIB, self.suffix)
+    }
+
+    /// Wasm module generated by wasm-bindgen (`<lib_name>_bg.wasm`)
+    pub fn module_file(&self) -> String {
+        format!("{}_bg.wasm", self.lib_name())
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// Wasm Build
+////////////////////////////////////////////////////////////////////////////////
+
+/// Remove everything from `wasm_pkg_path` (before building variants into it)
+pub fn clean_wasm_package(wasm_pkg_path: &Path) -> TaskResult {
+    if wasm_pkg_path.exists() {
+        fs::remove_dir_all(wasm_pkg_path)?;
+    }
+    make_each_directory(wasm_pkg_path)
+}
+
+/// Build shards-browser variant for wasm target with cargo and generate
+/// js-bindings with wasm-bindgen into `wasm_pkg_path`.
+pub fn build_wasm_bindgen_package(
+    wasm_pkg_path: &Path,
+    profile: WasmProfile,
+    variant: &WasmVariant,
+) -> TaskResult {
+    install_wasm_target(variant.toolchain);
+
+    let target_path = target_path(variant);
+    let target_path_arg = target_path.to_string_lossy();
+    let (cargo_profile, profile_dir) = cargo_profile(profile);
+    let mut args = vec![
+        "build",
+        "--package",
+        SHARDS_BROWSER_PACKAGE,
+        "--lib",
+        "--target",
+        WASM_TARGET,
+        "--profile",
+        cargo_profile,
+        "--target-dir",
+        &target_path_arg,
+    ];
+    args.extend(variant.cargo_args);
+    let env = [("RUSTFLAGS", variant.rustflags)];
+    match variant.toolchain {
+        Some(toolchain) => {
+            let toolchain_arg = format!("+{}", toolchain);
+            args.insert(0, &toolchain_arg);
+            shell_log_piped(CARGO_PROXY, &args, &env)?;
+        }
+        None => cargo_with_env(&args, &env)?,
+    }
+    let wasm_path = target_path
+        .join(WASM_TARGET)
+        .join(profile_dir)
+        .join(format!("{}.wasm", SHARDS_BROWSER_LIB));
+
+    ensure_wasm_bindgen()?;
+
+    let wasm_pkg_path_arg = wasm_pkg_path.to_string_lossy();
+    let wasm_path_arg = wasm_path.to_string_lossy();
+    let lib_name = variant.lib_name();
+    let mut args = vec![
+        "--target",
+        "web",
+        "--no-typescript",
+        "--out-dir",
+        &wasm_pkg_path_arg,
+        "--out-name",
+        &lib_name,
+    ];
+    if profile == WasmProfile::Dev {
+        args.extend(["--debug", "--keep-debug"]);
+    }
+    args.push(&wasm_path_arg);
+
+    shell_log_piped(WASM_BINDGEN, &args, &[])
+}
+
+/// Remove cargo artifacts of shards-browser variant (so next build compiles
+/// it from scratch).
+pub fn clean_wasm_build(
+    profile: WasmProfile,
+    variant: &WasmVariant,
+) -> TaskResult {
+    let target_path = target_path(variant);
+    if !target_path.exists() {
+        return OK;
+    }
+
+    let target_path_arg = target_path.to_string_lossy();
+    let (cargo_profile, _) = cargo_profile(profile);
+    cargo(&[
+        "clean",
+        "--package",
+        SHARDS_BROWSER_PACKAGE,
+        "--target",
+        WASM_TARGET,
+        "--profile",
+        cargo_profile,
+        "--target-dir",
+        &target_path_arg,
+    ])
+}
+
+/// Make sure installed wasm-bindgen CLI matches `wasm-bindgen` crate in
+/// `Cargo.lock` (installs matching one otherwise): generated bindings only
+/// work with the same version of the crate.
+pub fn ensure_wasm_bindgen() -> TaskResult {
+    let locked = locked_package_version(
+        &paths::PROJECT_ROOT.join(LOCK_FILE),
+        WASM_BINDGEN,
+    )?;
+
+    match installed_wasm_bindgen_version() {
+        Some(installed) if installed == locked => {
+            log::debug!("[xtask] wasm-bindgen {} is installed", installed);
+            return OK;
+        }
+        Some(installed) => log::info!(
+            "[xtask] wasm-bindgen {} is installed, but {} is locked",
+            installed,
+            locked
+        ),
+        None => log::info!("[xtask] wasm-bindgen is not installed"),
+    }
+
+    let version = format!("={}", locked);
+    cargo(&[
+        "install",
+        WASM_BINDGEN_CLI,
+        "--version",
+        &version,
+        "--locked",
+    ])?;
+
+    match installed_wasm_bindgen_version() {
+        Some(installed) if installed == locked => OK,
+        installed => Err(Error::other(format!(
+            "wasm-bindgen {} is required, but {} is in PATH",
+            locked,
+            installed.as_deref().unwrap_or("none")
+        ))),
+    }
+}
+
+/// Version of package in lock file
+///
+/// # Examples
+///
+/// ```
+/// use xtask::web_wasm_bindgen::locked_package_version;
+///
+/// let lock = std::env::temp_dir().join("xtask-doctest-Cargo.lock");
+/// std::fs::write(
+///     &lock,
+///     "[[package]]\nname = \"wasm-bindgen\"\nversion = \"0.2.100\"\n",
+/// )
+/// .unwrap();
+///
+/// assert_eq!(
+///     locked_package_version(&lock, "wasm-bindgen").unwrap(),
+///     "0.2.100"
+/// );
+/// assert!(locked_package_version(&lock, "egui").is_err());
+/// ```
+pub fn locked_package_version(lock_path: &Path, name: &str) -> Result<String> {
+    let lock: toml::Table = fs::read_to_string(lock_path)?
+        .parse()
+        .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+
+    lock.get("package")
+        .and_then(|packages| packages.as_array())
+        .into_iter()
+        .flatten()
+        .find(|package| {
+            package.get("name").and_then(|n| n.as_str()) == Some(name)
+        })
+        .and_then(|package| package.get("version"))
+        .and_then(|version| version.as_str())
+        .map(str::to_owned)
+        .ok_or_else(|| {
+            Error::new(
+                ErrorKind::NotFound,
+                format!("{} is not in {}", name, lock_path.display()),
+            )
+        })
+}
+
+/// `wasm-bindgen 0.2.100` -> `0.2.100` (`None` if not runnable)
+fn installed_wasm_bindgen_version() -> Option<String> {
+    let output = Command::new(WASM_BINDGEN).arg("--version").output().ok()?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    stdout.split_whitespace().nth(1).map(str::to_owned)
+}
+
+/// wasm-pack did it on its own, so keep doing it (not fatal: toolchain may be
+/// managed without rustup). Other toolchain is installed along with rust-src
+/// (to rebuild std).
+fn install_wasm_target(toolchain: Option<&str>) {
+    let result = match toolchain {
+        Some(toolchain) => shell_log_piped(
+            RUSTUP,
+            &[
+                "toolchain",
+                "install",
+                toolchain,
+                "--profile",
+                "minimal",
+                "--component",
+                "rust-src",
+                "--target",
+                WASM_TARGET,
+            ],
+            &[],
+        ),
+        None => shell_log_piped(RUSTUP, &["target", "add", WASM_TARGET], &[]),
+    };
+    if let Err(e) = result {
+        log::warn!(
+            "[xtask] Failed to add {} target via rustup: {}",
+            WASM_TARGET,
+            e
+        );
+    }
+}
+
+/// Separate target dir per variant: RUSTFLAGS change rebuilds everything
+fn target_path(variant: &WasmVariant) -> PathBuf {
+    if variant.is_baseline() {
+        paths::BUILD_PATH.clone()
+    } else {
+        paths::BUILD_PATH.join(format!("wasm{}", variant.suffix))
+    }
+}
+
+/// Cargo profile and its output directory
+fn cargo_profile(profile: WasmProfile) -> (&'static str, &'static str) {
+    match profile {
+        WasmProfile::Dev => ("dev", "debug"),
+        WasmProfile::Release => ("release", "release"),
+        // see root Cargo.toml
+        WasmProfile::Names => ("release-names", "release-names"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasmparser::{Validator, WasmFeatures};
+
+    /// `[0, 97, 115, 109, ...]` feature tests of front-page loader
+    fn front_page_feature_tests() -> Vec<Vec<u8>> {
+        let index = fs::read_to_string(
+            paths::PROJECT_ROOT.join("front-page").join("index.html"),
+        )
+        .unwrap();
+
+        index
+            .split('[')
+            .filter_map(|part| part.split_once(']'))
+            .map(|(list, _)| list)
+            .filter(|list| list.starts_with("0, 97, 115, 109,"))
+            .map(|list| {
+                list.split(',').map(|b| b.trim().parse().unwrap()).collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn front_page_detects_features_of_variants() {
+        let tests = front_page_feature_tests();
+        assert_eq!(tests.len(), 3);
+        assert!(WASM_VARIANTS[1].rustflags.contains("+simd128,+bulk-memory"));
+        assert!(WASM_VARIANTS[2].rustflags.contains("+atomics,+bulk-memory"));
+
+        let mvp = WasmFeatures::MUTABLE_GLOBAL;
+        let features = [
+            WasmFeatures::THREADS,
+            WasmFeatures::SIMD,
+            WasmFeatures::BULK_MEMORY,
+        ];
+        for (bytes, feature) in tests.iter().zip(features) {
+            assert!(
+                Validator::new_with_features(mvp)
+                    .validate_all(bytes)
+                    .is_err()
+            );
+            Validator::new_with_features(mvp | feature)
+                .validate_all(bytes)
+                .unwrap();
+        }
+    }
+}
@@ -0,0 +1,134 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{Error, Result};
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::core_dist::{DistributionPath, FailurePolicy, OK};
+use crate::web_template::Template;
+
+////////////////////////////////////////////////////////////////////////////////
+// Loader Settings
+////////////////////////////////////////////////////////////////////////////////
+
+/// Written into distribution root next to wasm packages
+pub const WASM_LOADER_FILE: &str = "shards_browser_loader.js";
+
+/// Source of loader (relative to front-page), expanded with `wasm_modules`
+/// variable
+pub(crate) const WASM_LOADER_TEMPLATE: &str = "partials/wasm-loader.js";
+
+/// wasm-bindgen names module of `<name>.js` as `<name>_bg.wasm`
+const WASM_MODULE_SUFFIX: &str = "_bg.wasm";
+
+////////////////////////////////////////////////////////////////////////////////
+// Loader
+////////////////////////////////////////////////////////////////////////////////
+
+/// Wasm module of variant as loader sees it (paths are relative to loader,
+/// fingerprinting rewrites them along with file names)
+#[derive(Debug, PartialEq, Serialize)]
+pub struct WasmModule {
+    /// wasm-bindgen glue module
+    pub glue: String,
+    pub path: String,
+    /// in bytes, progress is reported against it
+    pub size: u64,
+}
+
+/// Wasm modules of `web_dist_path` root (having glue module next to them)
+/// by library name (only built variants are listed, front-page picks one of
+/// them)
+///
+/// # Examples
+///
+/// ```
+/// use xtask::web_wasm_loader::wasm_modules;
+///
+/// let web_dist = std::env::temp_dir().join("xtask-doctest-wasm-loader");
+/// std::fs::create_dir_all(&web_dist).unwrap();
+/// std::fs::write(web_dist.join("a.js"), "").unwrap();
+/// std::fs::write(web_dist.join("a_bg.wasm"), "\0asm").unwrap();
+/// std::fs::write(web_dist.join("b_bg.wasm"), "\0asm").unwrap();
+///
+/// let modules = wasm_modules(&web_dist).unwrap();
+/// assert_eq!(modules.len(), 1);
+/// assert_eq!(modules["a"].glue, "./a.js");
+/// assert_eq!(modules["a"].path, "./a_bg.wasm");
+/// assert_eq!(modules["a"].size, 4);
+/// ```
+pub fn wasm_modules(
+    web_dist_path: &Path,
+) -> Result<BTreeMap<String, WasmModule>> {
+    let mut modules = BTreeMap::new();
+    web_dist_path.for_each_file_recursively(
+        FailurePolicy::FailFast,
+        |relative_path| {
+            let Some(name) = relative_path.to_str() else {
+                return OK;
+            };
+            let Some(lib_name) = name.strip_suffix(WASM_MODULE_SUFFIX) else {
+                return OK;
+            };
+            let glue = format!("{}.js", lib_name);
+            if lib_name.contains('/') || !web_dist_path.join(&glue).is_file() {
+                return OK;
+            }
+
+            modules.insert(
+                lib_name.to_owned(),
+                WasmModule {
+                    glue: format!("./{}", glue),
+                    path: format!("./{}", name),
+                    size: fs::metadata(web_dist_path.join(relative_path))?
+                        .len(),
+                },
+            );
+            OK
+        },
+    )?;
+    Ok(modules)
+}
+
+/// Source of loader for wasm modules of `web_dist_path` (expanded, not
+/// minified)
+pub fn wasm_loader_source(
+    web_dist_path: &Path,
+    front_page_path: &Path,
+) -> Result<String> {
+    let modules = wasm_modules(web_dist_path)?;
+    let template = Template {
+        root: front_page_path.to_owned(),
+        variables: [(
+            "wasm_modules".to_owned(),
+            serde_json::to_string(&modules).map_err(Error::other)?,
+        )]
+        .into(),
+        line_comments: false,
+    };
+    template.expand(
+        &fs::read_to_string(front_page_path.join(WASM_LOADER_TEMPLATE))?,
+        WASM_LOADER_TEMPLATE,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::web_dist::front_page_path;
+
+    #[test]
+    fn checked_in_loader_lists_modules() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("shards_browser.js"), "").unwrap();
+        fs::write(dir.path().join("shards_browser_bg.wasm"), [0; 10]).unwrap();
+
+        let loader =
+            wasm_loader_source(dir.path(), &front_page_path()).unwrap();
+        assert!(loader.contains(
+            r#"{"shards_browser":{"glue":"./shards_browser.js","path":"./shards_browser_bg.wasm","size":10}}"#
+        ));
+        assert!(!loader.contains("#echo"));
+    }
+}
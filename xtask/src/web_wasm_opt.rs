@@ -0,0 +1,193 @@
+use std::fs;
+use std::io::{Error, ErrorKind, Result};
+use std::path::{Path, PathBuf};
+
+use crate::core_dist::{
+    OK, TaskResult, cargo, shell_log_piped, task_flag, task_option,
+};
+use crate::paths;
+
+////////////////////////////////////////////////////////////////////////////////
+// Shell Commands
+////////////////////////////////////////////////////////////////////////////////
+
+const WASM_OPT: &str = "wasm-opt";
+
+////////////////////////////////////////////////////////////////////////////////
+// Optimization Settings
+////////////////////////////////////////////////////////////////////////////////
+
+/// Pass lists are read from `[package.metadata.xtask.wasm-opt]` of
+/// shards-browser manifest (`<profile> = ["-O", ...]`)
+const SHARDS_BROWSER_MANIFEST: &str = "shards-browser/Cargo.toml";
+
+/// Task options (of any task building wasm package)
+const PASSES_OPTION: &str = "--wasm-opt";
+const KEEP_UNOPTIMIZED_FLAG: &str = "--keep-unoptimized";
+const DEBUG_SIDECAR_FLAG: &str = "--debug-sidecar";
+
+/// Files next to `<module>.wasm` (never copied into distribution)
+pub(crate) const UNOPTIMIZED_SUFFIX: &str = ".unoptimized.wasm";
+pub(crate) const DEBUG_SIDECAR_SUFFIX: &str = ".debug.wasm";
+
+////////////////////////////////////////////////////////////////////////////////
+// Optimization Stage
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WasmProfile {
+    Dev,
+    Release,
+    /// optimized, keeping function names (size attribution)
+    Names,
+}
+
+impl WasmProfile {
+    pub fn name(&self) -> &'static str {
+        match self {
+            WasmProfile::Dev => "dev",
+            WasmProfile::Release => "release",
+            WasmProfile::Names => "names",
+        }
+    }
+}
+
+/// Run wasm-opt over `module_path` in place with passes of `profile`
+/// (overridable by `--wasm-opt "<passes>"`, e.g. `-O1` for dev builds) and
+/// wasm `features` of module.
+/// `--keep-unoptimized` keeps input as `<module>.unoptimized.wasm`,
+/// `--debug-sidecar` writes optimized module with names and DWARF as
+/// `<module>.debug.wasm` (served one is stripped).
+pub fn optimize_wasm_module(
+    module_path: &Path,
+    profile: WasmProfile,
+    features: &[&str],
+) -> TaskResult {
+    let mut passes: Vec<String> = match task_option(PASSES_OPTION) {
+        Some(passes) => passes.split_whitespace().map(str::to_owned).collect(),
+        None => load_passes(profile)?,
+    };
+    if passes.is_empty() {
+        log::info!(
+            "[xtask] No wasm-opt passes for {} profile, skipping",
+            profile.name()
+        );
+        return OK;
+    }
+    passes.extend(features.iter().map(|feature| feature.to_string()));
+
+    cargo(&["install", WASM_OPT])?;
+
+    let unoptimized_path = sibling_path(module_path, UNOPTIMIZED_SUFFIX);
+    let debug_sidecar_path = sibling_path(module_path, DEBUG_SIDECAR_SUFFIX);
+    fs::rename(module_path, &unoptimized_path)?;
+
+    let input = unoptimized_path.to_string_lossy();
+    let output = module_path.to_string_lossy();
+    let mut args: Vec<&str> = passes.iter().map(String::as_str).collect();
+
+    if task_flag(DEBUG_SIDECAR_FLAG) {
+        let debug_sidecar = debug_sidecar_path.to_string_lossy();
+        args.extend(["-g", &input, "-o", &debug_sidecar]);
+        shell_log_piped(WASM_OPT, &args, &[])?;
+
+        // same module without names and DWARF (features should match)
+        let mut strip_args: Vec<&str> = feature_flags(&passes).collect();
+        strip_args.extend(["--strip-debug", &debug_sidecar, "-o", &output]);
+        shell_log_piped(WASM_OPT, &strip_args, &[])?;
+        log::info!("[xtask] Debug sidecar: {}", debug_sidecar);
+    } else {
+        args.extend([input.as_ref(), "-o", &output]);
+        shell_log_piped(WASM_OPT, &args, &[])?;
+    }
+
+    let before = fs::metadata(&unoptimized_path)?.len();
+    let after = fs::metadata(module_path)?.len();
+    log::info!(
+        "[xtask] wasm-opt ({} profile, {}): {} B -> {} B ({:+.1}%)",
+        profile.name(),
+        passes.join(" "),
+        before,
+        after,
+        (after as f64 - before as f64) * 100.0 / before.max(1) as f64
+    );
+
+    if task_flag(KEEP_UNOPTIMIZED_FLAG) {
+        log::info!("[xtask] Unoptimized module: {}", input);
+    } else {
+        fs::remove_file(&unoptimized_path)?;
+    }
+
+    OK
+}
+
+/// Pass list of profile from shards-browser manifest (empty if not set)
+fn load_passes(profile: WasmProfile) -> Result<Vec<String>> {
+    let manifest_path = paths::PROJECT_ROOT.join(SHARDS_BROWSER_MANIFEST);
+    let manifest = toml::Value::Table(
+        fs::read_to_string(&manifest_path)?
+            .parse()
+            .map_err(|e| Error::new(ErrorKind::InvalidData, e))?,
+    );
+
+    let passes = ["package", "metadata", "xtask", "wasm-opt", profile.name()]
+        .iter()
+        .try_fold(&manifest, |value, key| value.get(key));
+    let Some(passes) = passes else {
+        return Ok(vec![]);
+    };
+
+    passes
+        .as_array()
+        .and_then(|passes| {
+            passes
+                .iter()
+                .map(|pass| pass.as_str().map(str::to_owned))
+                .collect()
+        })
+        .ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "{}: wasm-opt.{} should be list of strings",
+                    manifest_path.display(),
+                    profile.name()
+                ),
+            )
+        })
+}
+
+/// `--enable-*`/`--disable-*` flags of pass list
+fn feature_flags(passes: &[String]) -> impl Iterator<Item = &str> {
+    passes.iter().map(String::as_str).filter(|pass| {
+        pass.starts_with("--enable-") || pass.starts_with("--disable-")
+    })
+}
+
+/// `dir/name.wasm` -> `dir/name<suffix>`
+fn sibling_path(module_path: &Path, suffix: &str) -> PathBuf {
+    let stem = module_path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy())
+        .unwrap_or_default();
+    module_path.with_file_name(format!("{}{}", stem, suffix))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checked_in_pass_lists_are_valid() {
+        assert!(load_passes(WasmProfile::Dev).unwrap().is_empty());
+
+        let release = load_passes(WasmProfile::Release).unwrap();
+        assert!(!release.is_empty());
+        assert!(!release.iter().any(|pass| pass == "-g"));
+        // features are per wasm variant
+        assert_eq!(feature_flags(&release).count(), 0);
+
+        let names = load_passes(WasmProfile::Names).unwrap();
+        assert!(names.iter().any(|pass| pass == "-g"));
+    }
+}
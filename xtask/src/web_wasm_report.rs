@@ -0,0 +1,357 @@
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{Error, Result};
+use std::path::PathBuf;
+
+use serde::Serialize;
+use wasmparser::{KnownCustom, Name, Parser, Payload, TypeRef};
+
+use crate::core_dist::{OK, TaskResult, task_flag, task_option};
+use crate::paths;
+use crate::web_dist::build_wasm_names_package;
+use crate::web_wasm_bindgen::WASM_VARIANTS;
+
+////////////////////////////////////////////////////////////////////////////////
+// Report Settings
+////////////////////////////////////////////////////////////////////////////////
+
+const WASM_REPORT_FILE: &str = "wasm-report.json";
+
+const DEFAULT_TOP_FUNCTIONS: usize = 40;
+
+/// Pseudo crates of code without (parsable) Rust symbol
+const UNKNOWN_CRATE: &str = "[unknown]";
+const WASM_BINDGEN_CRATE: &str = "[wasm-bindgen]";
+const DATA_CRATE: &str = "[data]";
+
+////////////////////////////////////////////////////////////////////////////////
+// CLI Tasks
+////////////////////////////////////////////////////////////////////////////////
+
+/// Report what shards-browser wasm consists of: code size per function and
+/// per crate (text table and `target/wasm-report.json`).
+/// Accepts `--wasm <path>` (baseline module of release package by default),
+/// `--names` (build and report module keeping function names) and
+/// `--top <count>`.
+pub fn wasm_report() -> TaskResult {
+    let module_file = WASM_VARIANTS[0].module_file();
+    let wasm_path = if task_flag("--names") {
+        let names_pkg_path =
+            paths::BUILD_PATH.join(paths::WASM_PKG_NAMES_SUBDIRECTORY);
+        build_wasm_names_package(&names_pkg_path)?;
+        names_pkg_path.join(&module_file)
+    } else {
+        task_option("--wasm").map(PathBuf::from).unwrap_or_else(|| {
+            paths::BUILD_PATH
+                .join(paths::WASM_PKG_SUBDIRECTORY)
+                .join(&module_file)
+        })
+    };
+    let top = match task_option("--top") {
+        Some(top) => top.parse().map_err(Error::other)?,
+        None => DEFAULT_TOP_FUNCTIONS,
+    };
+
+    log::info!("[xtask] Analyzing {}", wasm_path.display());
+    let report = WasmReport::analyze(&fs::read(&wasm_path)?)?;
+
+    if !report.has_names {
+        log::warn!(
+            "[xtask] {} has no function names, run with `--names` for \
+             per-crate attribution",
+            wasm_path.display()
+        );
+    }
+    log_report(&report, top);
+
+    let report_path = paths::BUILD_PATH.join(WASM_REPORT_FILE);
+    log::info!("[xtask] Writing {}", report_path.display());
+    fs::write(
+        report_path,
+        serde_json::to_string_pretty(&report).map_err(Error::other)?,
+    )?;
+
+    OK
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// Attribution
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Serialize)]
+pub struct FunctionSize {
+    pub name: String,
+    #[serde(rename = "crate")]
+    pub crate_name: String,
+    pub size: u64,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct CrateSize {
+    pub name: String,
+    pub size: u64,
+    pub functions: usize,
+}
+
+/// Module sizes: whole file, code and data sections, per function (largest
+/// first) and per crate (largest first, data segments as `[data]`)
+#[derive(Debug, Default, Serialize)]
+pub struct WasmReport {
+    pub total: u64,
+    pub code: u64,
+    pub data: u64,
+    pub has_names: bool,
+    pub crates: Vec<CrateSize>,
+    pub functions: Vec<FunctionSize>,
+}
+
+impl WasmReport {
+    pub fn analyze(wasm: &[u8]) -> Result<Self> {
+        let mut imported_functions = 0;
+        let mut bodies = vec![];
+        let mut names = BTreeMap::new();
+        let mut report = WasmReport {
+            total: wasm.len() as u64,
+            ..Default::default()
+        };
+
+        for payload in Parser::new(0).parse_all(wasm) {
+            match payload.map_err(Error::other)? {
+                Payload::ImportSection(imports) => {
+                    for import in imports.into_imports() {
+                        if let TypeRef::Func(_) | TypeRef::FuncExact(_) =
+                            import.map_err(Error::other)?.ty
+                        {
+                            imported_functions += 1;
+                        }
+                    }
+                }
+                Payload::CodeSectionStart { size, .. } => {
+                    report.code = size as u64
+                }
+                Payload::CodeSectionEntry(body) => {
+                    let range = body.range();
+                    bodies.push(range.end - range.start);
+                }
+                Payload::DataSection(data) => {
+                    let range = data.range();
+                    report.data = range.end - range.start;
+                }
+                Payload::CustomSection(custom) => {
+                    if let KnownCustom::Name(name_section) = custom.as_known() {
+                        for name in name_section {
+                            if let Ok(Name::Function(function_names)) = name {
+                                for naming in
+                                    function_names.into_iter().flatten()
+                                {
+                                    names.insert(naming.index, naming.name);
+                                }
+                            }
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        report.has_names = !names.is_empty();
+
+        let mut crates: BTreeMap<String, CrateSize> = BTreeMap::new();
+        for (local_index, size) in bodies.into_iter().enumerate() {
+            let index = imported_functions + local_index as u32;
+            let (name, crate_name) = match names.get(&index) {
+                Some(symbol) => {
+                    let name =
+                        format!("{:#}", rustc_demangle::demangle(symbol));
+                    let crate_name = crate_of(&name)
+                        .map(str::to_owned)
+                        .unwrap_or_else(|| pseudo_crate_of(symbol).to_owned());
+                    (name, crate_name)
+                }
+                None => (format!("func[{}]", index), UNKNOWN_CRATE.to_owned()),
+            };
+
+            let crate_size = crates.entry(crate_name.clone()).or_default();
+            crate_size.size += size;
+            crate_size.functions += 1;
+
+            report.functions.push(FunctionSize {
+                name,
+                crate_name,
+                size,
+            });
+        }
+        if report.data > 0 {
+            crates.insert(
+                DATA_CRATE.to_owned(),
+                CrateSize {
+                    size: report.data,
+                    ..Default::default()
+                },
+            );
+        }
+
+        report.crates = crates
+            .into_iter()
+            .map(|(name, size)| CrateSize { name, ..size })
+            .collect();
+        report.crates.sort_by_key(|size| Reverse(size.size));
+        report
+            .functions
+            .sort_by_key(|function| Reverse(function.size));
+
+        Ok(report)
+    }
+}
+
+/// Crate of demangled Rust symbol: first path segment, for trait impls of
+/// foreign types (`<&T as core::fmt::Debug>::fmt`) crate of trait.
+///
+/// # Examples
+///
+/// ```
+/// use xtask::web_wasm_report::crate_of;
+///
+/// assert_eq!(crate_of("egui::context::Context::run"), Some("egui"));
+/// assert_eq!(
+///     crate_of("<wgpu::Device as core::ops::drop::Drop>::drop"),
+///     Some("wgpu")
+/// );
+/// assert_eq!(crate_of("<&T as core::fmt::Debug>::fmt"), Some("core"));
+/// assert_eq!(crate_of("<[u8] as alloc::borrow::ToOwned>::f"), Some("alloc"));
+/// assert_eq!(crate_of("memcpy"), None);
+/// ```
+pub fn crate_of(symbol: &str) -> Option<&str> {
+    path_crate(symbol).or_else(|| {
+        symbol
+            .split_once(" as ")
+            .and_then(|(_, trait_path)| path_crate(trait_path))
+    })
+}
+
+fn path_crate(path: &str) -> Option<&str> {
+    let mut path = path.trim_start_matches(['<', '&', '*', '[', '(']);
+    for prefix in ["mut ", "const ", "dyn "] {
+        path = path.strip_prefix(prefix).unwrap_or(path);
+    }
+
+    let (head, _) = path.split_once("::")?;
+    let is_identifier = !head.is_empty()
+        && head.chars().all(|c| c.is_alphanumeric() || c == '_');
+    is_identifier.then_some(head)
+}
+
+/// Pseudo crate of non Rust symbol
+fn pseudo_crate_of(symbol: &str) -> &'static str {
+    if symbol.starts_with("__wbg") || symbol.starts_with("__wbindgen") {
+        WASM_BINDGEN_CRATE
+    } else {
+        UNKNOWN_CRATE
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// Logging
+////////////////////////////////////////////////////////////////////////////////
+
+fn log_report(report: &WasmReport, top: usize) {
+    let percent = |size: u64| size as f64 * 100.0 / report.total.max(1) as f64;
+
+    log::info!(
+        "[xtask] Module: {} B total, {} B code ({:.1}%), {} B data ({:.1}%)",
+        report.total,
+        report.code,
+        percent(report.code),
+        report.data,
+        percent(report.data)
+    );
+
+    let crate_width = report
+        .crates
+        .iter()
+        .map(|size| size.name.len())
+        .max()
+        .unwrap_or_default()
+        .max("crate".len());
+    log::info!("[xtask] By crate:");
+    log::info!(
+        "[xtask] {:crate_width$} {:>10} {:>7} {:>10}",
+        "crate",
+        "size",
+        "%",
+        "functions"
+    );
+    for size in report.crates.iter() {
+        log::info!(
+            "[xtask] {:crate_width$} {:>10} {:>6.1}% {:>10}",
+            size.name,
+            size.size,
+            percent(size.size),
+            size.functions
+        );
+    }
+
+    log::info!(
+        "[xtask] Top {} of {} functions:",
+        top.min(report.functions.len()),
+        report.functions.len()
+    );
+    log::info!("[xtask] {:>10} {:>7}  function", "size", "%");
+    for function in report.functions.iter().take(top) {
+        log::info!(
+            "[xtask] {:>10} {:>6.2}%  {}",
+            function.size,
+            percent(function.size),
+            function.name
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn section(id: u8, payload: &[u8]) -> Vec<u8> {
+        let mut section = vec![id, payload.len() as u8];
+        section.extend_from_slice(payload);
+        section
+    }
+
+    fn name(name: &str) -> Vec<u8> {
+        let mut bytes = vec![name.len() as u8];
+        bytes.extend_from_slice(name.as_bytes());
+        bytes
+    }
+
+    #[test]
+    fn it_attributes_functions_by_name_section() {
+        let mut function_names = vec![2, 1];
+        function_names.extend(name("__wbindgen_malloc"));
+        function_names.push(2);
+        function_names.extend(name("_ZN4egui3run17h0123456789abcdefE"));
+        let mut name_section = name("name");
+        name_section.extend(section(1, &function_names));
+
+        let wasm = [
+            b"\0asm\x01\0\0\0".to_vec(),
+            section(1, &[1, 0x60, 0, 0]),
+            section(2, &[1, 1, b'm', 1, b'f', 0, 0]),
+            section(3, &[2, 0, 0]),
+            section(10, &[2, 2, 0, 0x0b, 4, 0, 1, 1, 0x0b]),
+            section(0, &name_section),
+        ]
+        .concat();
+
+        let report = WasmReport::analyze(&wasm).unwrap();
+
+        assert!(report.has_names);
+        assert_eq!(report.functions[0].name, "egui::run");
+        assert_eq!(report.functions[0].crate_name, "egui");
+        assert_eq!(report.functions[1].crate_name, WASM_BINDGEN_CRATE);
+        assert_eq!(report.crates[0].name, "egui");
+        assert!(
+            report.crates.iter().map(|c| c.size).sum::<u64>() <= report.code
+        );
+    }
+}
@@ -0,0 +1,199 @@
+use std::collections::BTreeSet;
+use std::io::{Error, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::Duration;
+
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+
+use crate::core_dist::{OK, TaskResult};
+use crate::paths;
+use crate::web_dist::{
+    assemble_web_distribution, build_front_page_file, build_wasm_loader,
+    build_wasm_package, build_web_distribution_by_path, copy_wasm_package,
+    front_page_path,
+};
+
+////////////////////////////////////////////////////////////////////////////////
+// Watched Paths
+////////////////////////////////////////////////////////////////////////////////
+
+const WATCHED_RUST_SOURCE_DIRS: &[&str] =
+    &["shards-browser/src", "shards-logger/src"];
+
+/// Quiet period closing a burst of file system events (editors tend to
+/// write, rename and chmod in one go)
+const DEBOUNCE_DURATION: Duration = Duration::from_millis(250);
+
+////////////////////////////////////////////////////////////////////////////////
+// CLI Tasks
+////////////////////////////////////////////////////////////////////////////////
+
+/// Release post-processing (fingerprints, font subsets, CSP hashes, service
+/// worker) covers whole distribution: every change re-assembles it
+pub fn watch_web_distribution() -> TaskResult {
+    let web_dist_path = paths::BUILD_PATH.join(paths::WEB_DIST_SUBDIRECTORY);
+    let wasm_pkg_path = paths::BUILD_PATH.join(paths::WASM_PKG_SUBDIRECTORY);
+    watch_web_distribution_by_path(&web_dist_path, &wasm_pkg_path, true, |_| {})
+}
+
+pub fn watch_web_distribution_dev() -> TaskResult {
+    let web_dist_path =
+        paths::BUILD_PATH.join(paths::WEB_DIST_DEV_SUBDIRECTORY);
+    let wasm_pkg_path =
+        paths::BUILD_PATH.join(paths::WASM_PKG_DEV_SUBDIRECTORY);
+    watch_web_distribution_by_path(
+        &web_dist_path,
+        &wasm_pkg_path,
+        false,
+        |_| {},
+    )
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// Watch Changes
+////////////////////////////////////////////////////////////////////////////////
+
+/// Changes collected from one debounced burst of events
+#[derive(Default)]
+pub struct WatchChanges {
+    pub rust_sources: bool,
+    // relative to front-page
+    pub front_page_files: BTreeSet<PathBuf>,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// Private
+////////////////////////////////////////////////////////////////////////////////
+
+/// Build distribution, then rebuild changed parts forever.
+/// `on_rebuilt` is called after each successful rebuild.
+pub(crate) fn watch_web_distribution_by_path<F>(
+    web_dist_path: &Path,
+    wasm_pkg_path: &Path,
+    release: bool,
+    mut on_rebuilt: F,
+) -> TaskResult
+where
+    F: FnMut(&WatchChanges),
+{
+    build_web_distribution_by_path(web_dist_path, wasm_pkg_path, release)?;
+
+    let front_page_path = front_page_path();
+    let rust_source_paths: Vec<_> = WATCHED_RUST_SOURCE_DIRS
+        .iter()
+        .map(|dir| paths::PROJECT_ROOT.join(dir))
+        .collect();
+
+    let (sender, receiver) = mpsc::channel();
+    let mut watcher =
+        notify::recommended_watcher(sender).map_err(Error::other)?;
+
+    for path in rust_source_paths.iter().chain([&front_page_path]) {
+        log::info!("[xtask] Watching {}", path.display());
+        watcher
+            .watch(path, RecursiveMode::Recursive)
+            .map_err(Error::other)?;
+    }
+
+    loop {
+        let mut changes = WatchChanges::default();
+
+        // block until first event, then drain burst until quiet period
+        let mut next_event = receiver.recv().map_err(Error::other);
+        loop {
+            match next_event? {
+                Ok(event) => collect_changes(
+                    &mut changes,
+                    event,
+                    &front_page_path,
+                    &rust_source_paths,
+                ),
+                Err(e) => log::error!("[xtask] Watch error: {}", e),
+            }
+            next_event = match receiver.recv_timeout(DEBOUNCE_DURATION) {
+                Ok(event) => Ok(event),
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => {
+                    Err(Error::from(ErrorKind::BrokenPipe))
+                }
+            };
+        }
+
+        if !changes.rust_sources && changes.front_page_files.is_empty() {
+            continue;
+        }
+
+        // note: failed rebuild should not stop watching
+        match rebuild_changes(&changes, web_dist_path, wasm_pkg_path, release) {
+            Ok(()) => on_rebuilt(&changes),
+            Err(e) => log::error!("[xtask] Rebuild failed: {}", e),
+        }
+    }
+}
+
+fn collect_changes(
+    changes: &mut WatchChanges,
+    event: Event,
+    front_page_path: &Path,
+    rust_source_paths: &[PathBuf],
+) {
+    if matches!(event.kind, EventKind::Access(_)) {
+        return;
+    }
+
+    for path in event.paths.iter().filter(|path| !is_editor_artifact(path)) {
+        if rust_source_paths.iter().any(|dir| path.starts_with(dir)) {
+            changes.rust_sources = true;
+        } else if let Ok(relative_path) = path.strip_prefix(front_page_path)
+            && !path.is_dir()
+        {
+            changes.front_page_files.insert(relative_path.to_owned());
+        }
+    }
+}
+
+fn rebuild_changes(
+    changes: &WatchChanges,
+    web_dist_path: &Path,
+    wasm_pkg_path: &Path,
+    release: bool,
+) -> TaskResult {
+    if changes.rust_sources {
+        log::info!("[xtask] Rust sources changed, rebuilding wasm package");
+        build_wasm_package(wasm_pkg_path, release)?;
+    }
+
+    if release {
+        // post-processing (fingerprints, etc.) needs whole distribution
+        assemble_web_distribution(web_dist_path, wasm_pkg_path, release)?;
+    } else {
+        if changes.rust_sources {
+            copy_wasm_package(web_dist_path, wasm_pkg_path, release)?;
+            build_wasm_loader(web_dist_path, release)?;
+        }
+        for relative_path in changes.front_page_files.iter() {
+            log::info!(
+                "[xtask] Front-page changed: {}",
+                relative_path.display()
+            );
+            build_front_page_file(web_dist_path, relative_path, release)?;
+        }
+    }
+
+    log::info!("[xtask] Rebuilt! Check: {}", web_dist_path.display());
+    OK
+}
+
+/// Swap/backup files written by editors next to the edited one
+fn is_editor_artifact(path: &Path) -> bool {
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy())
+        .unwrap_or_default();
+
+    file_name.ends_with('~')
+        || file_name.ends_with(".swp")
+        || file_name.ends_with(".swx")
+        || file_name.starts_with(".#")
+}
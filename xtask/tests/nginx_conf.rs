@@ -0,0 +1,42 @@
+//! Golden-file test for generated nginx config.
+//!
+//! Run with `XTASK_BLESS=1` to update golden file after intended changes.
+
+use std::{env, fs, path::Path};
+
+use xtask::paths;
+use xtask::web_headers::HeaderRules;
+use xtask::web_nginx::{NginxSettings, error_page_codes, render_nginx_conf};
+
+const GOLDEN_NGINX_CONF: &str = "tests/golden/nginx.conf";
+
+#[test]
+fn nginx_conf_matches_golden_file() {
+    let settings = NginxSettings {
+        server_name: "matht.in".to_owned(),
+        root: "/var/www/matht.in".to_owned(),
+        listen: "80".to_owned(),
+        brotli: true,
+    };
+    let header_rules = HeaderRules::load(&paths::HEADER_RULES_PATH).unwrap();
+    let error_codes = error_page_codes(
+        &paths::PROJECT_ROOT.join("front-page").join("error_pages"),
+    )
+    .unwrap();
+
+    let conf = render_nginx_conf(&settings, &header_rules, &error_codes);
+
+    let golden_path =
+        Path::new(env!("CARGO_MANIFEST_DIR")).join(GOLDEN_NGINX_CONF);
+    if env::var_os("XTASK_BLESS").is_some() {
+        fs::write(&golden_path, &conf).unwrap();
+    }
+    let golden = fs::read_to_string(&golden_path).unwrap();
+
+    assert!(
+        conf == golden,
+        "generated nginx config differs from {} (rerun with XTASK_BLESS=1 if intended):\n{}",
+        golden_path.display(),
+        conf
+    );
+}